@@ -3,9 +3,11 @@ extern crate lazy_static;
 
 use anyhow::{anyhow, Context, Error, Result};
 use regex::Regex;
+use serde::Serialize;
 use std::{
-    collections::HashMap,
-    fs::File,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+    fs::{self, File},
     io::{self, BufRead, BufReader, Write},
     str::FromStr,
 };
@@ -43,13 +45,35 @@ impl<'claim> Iterator for IterPoints<'claim> {
 }
 
 impl Claim {
-    fn points(&self) -> IterPoints {
+    fn points(&self) -> IterPoints<'_> {
         IterPoints {
             claim: self,
             curr_x: self.x,
             curr_y: self.y,
         }
     }
+
+    fn is_on_border(&self, (x, y): (u32, u32)) -> bool {
+        let inside =
+            x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height;
+        inside
+            && (x == self.x
+                || x == self.x + self.width - 1
+                || y == self.y
+                || y == self.y + self.height - 1)
+    }
+
+    fn overlap_area(&self, other: &Claim) -> u32 {
+        let left = self.x.max(other.x);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let top = self.y.max(other.y);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        if left < right && top < bottom {
+            (right - left) * (bottom - top)
+        } else {
+            0
+        }
+    }
 }
 
 impl FromStr for Claim {
@@ -100,16 +124,185 @@ fn calculte_overlap_area(grid: &Grid) -> usize {
     grid.values().filter(|&&v| v > 1).count()
 }
 
-fn find_first_non_overlap_claim<'claim, 'grid>(
+fn find_first_non_overlap_claim<'claim>(
     claims: &'claim [Claim],
-    grid: &'grid Grid,
+    grid: &Grid,
 ) -> Option<&'claim Claim> {
-    for claim in claims {
-        if claim.points().all(|point| grid[&point] == 1) {
-            return Some(claim);
+    claims
+        .iter()
+        .find(|claim| claim.points().all(|point| grid[&point] == 1))
+}
+
+#[derive(Serialize)]
+struct ClaimOverlap {
+    id: u32,
+    area: u32,
+}
+
+#[derive(Serialize)]
+struct OverlapGraph {
+    overlaps: BTreeMap<u32, Vec<ClaimOverlap>>,
+}
+
+#[derive(Serialize)]
+struct OverlapReport<'graph> {
+    intact: Vec<u32>,
+    clusters: Vec<Vec<u32>>,
+    overlaps: &'graph BTreeMap<u32, Vec<ClaimOverlap>>,
+}
+
+impl OverlapGraph {
+    fn new(claims: &[Claim]) -> Self {
+        let mut overlaps = claims
+            .iter()
+            .map(|claim| (claim.id, Vec::new()))
+            .collect::<BTreeMap<_, _>>();
+        for (idx, claim1) in claims.iter().enumerate() {
+            for claim2 in &claims[idx + 1..] {
+                let area = claim1.overlap_area(claim2);
+                if area > 0 {
+                    overlaps.entry(claim1.id).or_default().push(ClaimOverlap {
+                        id: claim2.id,
+                        area,
+                    });
+                    overlaps.entry(claim2.id).or_default().push(ClaimOverlap {
+                        id: claim1.id,
+                        area,
+                    });
+                }
+            }
+        }
+        overlaps
+            .values_mut()
+            .for_each(|neighbours| neighbours.sort_by_key(|overlap| overlap.id));
+
+        OverlapGraph { overlaps }
+    }
+
+    fn intact_claims(&self) -> Vec<u32> {
+        self.overlaps
+            .iter()
+            .filter(|(_, neighbours)| neighbours.is_empty())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    fn overlapping(&self, id: u32) -> Option<&[ClaimOverlap]> {
+        self.overlaps
+            .get(&id)
+            .map(|neighbours| neighbours.as_slice())
+    }
+
+    fn conflict_clusters(&self) -> Vec<Vec<u32>> {
+        let mut visited = BTreeSet::new();
+        let mut clusters = Vec::new();
+        for (&id, neighbours) in &self.overlaps {
+            if neighbours.is_empty() || visited.contains(&id) {
+                continue;
+            }
+            let mut cluster = Vec::new();
+            let mut stack = vec![id];
+            visited.insert(id);
+            while let Some(curr) = stack.pop() {
+                cluster.push(curr);
+                for overlap in &self.overlaps[&curr] {
+                    if visited.insert(overlap.id) {
+                        stack.push(overlap.id);
+                    }
+                }
+            }
+            cluster.sort_unstable();
+            clusters.push(cluster);
+        }
+
+        clusters
+    }
+
+    fn to_json(&self) -> Result<String> {
+        let report = OverlapReport {
+            intact: self.intact_claims(),
+            clusters: self.conflict_clusters(),
+            overlaps: &self.overlaps,
+        };
+        serde_json::to_string_pretty(&report).context("failed to serialize overlap graph")
+    }
+}
+
+struct Heatmap<'claim> {
+    grid: &'claim Grid,
+    outlines: Vec<&'claim Claim>,
+    width: u32,
+    height: u32,
+}
+
+impl<'claim> Heatmap<'claim> {
+    fn new(claims: &'claim [Claim], grid: &'claim Grid) -> Self {
+        let width = claims.iter().map(|c| c.x + c.width).max().unwrap_or(0);
+        let height = claims.iter().map(|c| c.y + c.height).max().unwrap_or(0);
+        let outlines = claims
+            .iter()
+            .filter(|claim| claim.points().all(|point| grid[&point] == 1))
+            .collect();
+
+        Heatmap {
+            grid,
+            outlines,
+            width,
+            height,
+        }
+    }
+
+    fn count(&self, point: (u32, u32)) -> u32 {
+        self.grid.get(&point).cloned().unwrap_or(0)
+    }
+
+    fn is_outline(&self, point: (u32, u32)) -> bool {
+        self.outlines.iter().any(|claim| claim.is_on_border(point))
+    }
+
+    fn color(&self, point: (u32, u32)) -> (u8, u8, u8) {
+        if self.is_outline(point) {
+            return (0, 255, 0);
+        }
+        match self.count(point) {
+            0 => (16, 16, 16),
+            1 => (40, 70, 160),
+            2 => (230, 200, 40),
+            3 => (240, 130, 30),
+            4 => (220, 60, 30),
+            _ => (255, 255, 255),
         }
     }
-    None
+
+    fn render_ppm(&self) -> Vec<u8> {
+        let mut image = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        image.reserve((3 * self.width * self.height) as usize);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (r, g, b) = self.color((x, y));
+                image.extend_from_slice(&[r, g, b]);
+            }
+        }
+        image
+    }
+
+    fn render_terminal(&self, x: u32, y: u32, width: u32, height: u32) -> String {
+        let mut rendered = String::new();
+        for y in y..(y + height).min(self.height) {
+            for x in x..(x + width).min(self.width) {
+                let count = self.count((x, y));
+                let symbol = match count {
+                    0 => '.',
+                    1..=9 => std::char::from_digit(count, 10).unwrap(),
+                    _ => '+',
+                };
+                let (r, g, b) = self.color((x, y));
+                rendered.push_str(&format!("\x1b[48;2;{};{};{}m{}", r, g, b, symbol));
+            }
+            rendered.push_str("\x1b[0m\n");
+        }
+        rendered
+    }
 }
 
 fn main() -> Result<()> {
@@ -129,5 +322,52 @@ fn main() -> Result<()> {
         writeln!(io::stdout(), "no overlap claim found")?;
     }
 
+    let graph = OverlapGraph::new(&claims);
+    writeln!(io::stdout(), "intact claims: {:?}", graph.intact_claims())?;
+    let clusters = graph.conflict_clusters();
+    writeln!(
+        io::stdout(),
+        "conflict clusters: {}, largest has {} claims",
+        clusters.len(),
+        clusters
+            .iter()
+            .map(|cluster| cluster.len())
+            .max()
+            .unwrap_or(0)
+    )?;
+    if let Some(id) = env::args().skip_while(|arg| arg != "--overlapping").nth(1) {
+        let id = id.parse().context("invalid claim id")?;
+        match graph.overlapping(id) {
+            Some(neighbours) => {
+                for overlap in neighbours {
+                    writeln!(
+                        io::stdout(),
+                        "claim #{} overlaps #{} by {} square inches",
+                        id,
+                        overlap.id,
+                        overlap.area
+                    )?;
+                }
+            }
+            None => writeln!(io::stderr(), "claim #{} not found", id)?,
+        }
+    }
+    if let Some(path) = env::args().skip_while(|arg| arg != "--json").nth(1) {
+        fs::write(&path, graph.to_json()?).context("failed to write overlap graph")?;
+    }
+
+    let heatmap = Heatmap::new(&claims, &grid);
+    if let Some(path) = env::args().skip_while(|arg| arg != "--heatmap").nth(1) {
+        fs::write(&path, heatmap.render_ppm()).context("failed to write heatmap")?;
+    }
+    if env::args().any(|arg| arg == "--heatmap-text") {
+        if let Some(claim) = find_first_non_overlap_claim(&claims, &grid) {
+            let x = claim.x.saturating_sub(5);
+            let y = claim.y.saturating_sub(5);
+            let rendered = heatmap.render_terminal(x, y, claim.width + 10, claim.height + 10);
+            write!(io::stdout(), "{}", rendered)?;
+        }
+    }
+
     Ok(())
 }
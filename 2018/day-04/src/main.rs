@@ -5,7 +5,8 @@ use anyhow::{anyhow, Context, Error, Result};
 use regex::Regex;
 use std::{
     collections::HashMap,
-    fs::File,
+    env, fmt,
+    fs::{self, File},
     io::{self, BufRead, BufReader, Write},
     str::FromStr,
 };
@@ -19,6 +20,71 @@ struct DateTime {
     minute: u32,
 }
 
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+struct Date {
+    year: u32,
+    month: u32,
+    day: u32,
+}
+
+impl Date {
+    fn days_in_month(&self) -> u32 {
+        match self.month {
+            2 if self.year.is_multiple_of(4)
+                && (!self.year.is_multiple_of(100) || self.year.is_multiple_of(400)) =>
+            {
+                29
+            }
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    fn next_day(&self) -> Date {
+        if self.day < self.days_in_month() {
+            Date {
+                day: self.day + 1,
+                ..*self
+            }
+        } else if self.month < 12 {
+            Date {
+                year: self.year,
+                month: self.month + 1,
+                day: 1,
+            }
+        } else {
+            Date {
+                year: self.year + 1,
+                month: 1,
+                day: 1,
+            }
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl DateTime {
+    fn date(&self) -> Date {
+        Date {
+            year: self.year,
+            month: self.month,
+            day: self.day,
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:02}:{:02}", self.date(), self.hour, self.minute)
+    }
+}
+
 type GuardID = u32;
 
 enum EventKind {
@@ -79,105 +145,323 @@ impl FromStr for GuardEvent {
     }
 }
 
-fn aggregate_minutes_sleep_per_guard(
-    guard_events: &[GuardEvent],
-) -> Result<HashMap<GuardID, [u32; 60]>> {
-    let mut aggregates = HashMap::new();
+struct Shift {
+    date: Date,
+    guard_id: GuardID,
+    naps: Vec<(u32, u32)>,
+}
+
+fn build_shifts(guard_events: &[GuardEvent]) -> Result<Vec<Shift>> {
+    let mut shifts: Vec<Shift> = Vec::new();
+    let mut current_asleep: Option<&DateTime> = None;
 
-    let mut current_guard = None;
-    let mut current_asleep = None;
     for event in guard_events {
+        let datetime = &event.datetime;
         match event.kind {
-            EventKind::StartShift { guard_id } => current_guard = Some(guard_id),
-            EventKind::Asleep => {
-                if current_guard.is_none() {
-                    return Err(anyhow!("unordered event"));
+            EventKind::StartShift { guard_id } => {
+                if let (Some(asleep), Some(shift)) = (current_asleep, shifts.last()) {
+                    return Err(anyhow!(
+                        "guard #{} still asleep since {} when guard #{} begins shift at {}",
+                        shift.guard_id,
+                        asleep,
+                        guard_id,
+                        datetime
+                    ));
                 }
-                current_asleep = Some(event.datetime.minute);
+                let date = match datetime.hour {
+                    0 => datetime.date(),
+                    23 => datetime.date().next_day(),
+                    _ => {
+                        return Err(anyhow!(
+                            "guard #{} begins shift at {}, outside 23:00-00:59",
+                            guard_id,
+                            datetime
+                        ))
+                    }
+                };
+                shifts.push(Shift {
+                    date,
+                    guard_id,
+                    naps: vec![],
+                });
             }
-            EventKind::Wakeup => {
-                if current_guard.is_none() || current_asleep.is_none() {
-                    return Err(anyhow!("unordered event"));
+            EventKind::Asleep | EventKind::Wakeup => {
+                let shift = shifts
+                    .last_mut()
+                    .ok_or_else(|| anyhow!("event at {} before any shift begins", datetime))?;
+                if datetime.hour != 0 {
+                    return Err(anyhow!(
+                        "guard #{} sleep event at {} outside 00:00-00:59",
+                        shift.guard_id,
+                        datetime
+                    ));
                 }
-                let guard_id = current_guard.unwrap();
-                let asleep = current_asleep.unwrap();
-                let wakeup = event.datetime.minute;
-                let freq_sleep_minutes = aggregates.entry(guard_id).or_insert([0; 60]);
-
-                if wakeup < asleep {
-                    for minute in asleep..=59 {
-                        freq_sleep_minutes[minute as usize] += 1;
+                if datetime.date() != shift.date {
+                    return Err(anyhow!(
+                        "guard #{} sleep event at {} does not belong to shift on {}",
+                        shift.guard_id,
+                        datetime,
+                        shift.date
+                    ));
+                }
+                match (&event.kind, current_asleep) {
+                    (EventKind::Asleep, None) => current_asleep = Some(datetime),
+                    (EventKind::Asleep, Some(asleep)) => {
+                        return Err(anyhow!(
+                            "guard #{} falls asleep at {} while already asleep since {}",
+                            shift.guard_id,
+                            datetime,
+                            asleep
+                        ))
                     }
-
-                    for minute in 0..wakeup {
-                        freq_sleep_minutes[minute as usize] += 1;
+                    (_, None) => {
+                        return Err(anyhow!(
+                            "guard #{} wakes up at {} without falling asleep",
+                            shift.guard_id,
+                            datetime
+                        ))
                     }
-                } else {
-                    for minute in asleep..wakeup {
-                        freq_sleep_minutes[minute as usize] += 1;
+                    (_, Some(asleep)) => {
+                        if datetime.minute <= asleep.minute {
+                            return Err(anyhow!(
+                                "guard #{} wakes up at {} before falling asleep at {}",
+                                shift.guard_id,
+                                datetime,
+                                asleep
+                            ));
+                        }
+                        shift.naps.push((asleep.minute, datetime.minute));
+                        current_asleep = None;
                     }
                 }
             }
         }
     }
+    if let (Some(asleep), Some(shift)) = (current_asleep, shifts.last()) {
+        return Err(anyhow!(
+            "guard #{} still asleep since {} at end of shift",
+            shift.guard_id,
+            asleep
+        ));
+    }
 
-    Ok(aggregates)
+    Ok(shifts)
 }
 
-fn find_most_sleep_guard(aggregates: &HashMap<GuardID, [u32; 60]>) -> Option<&GuardID> {
+fn aggregate_minutes_sleep_per_guard(shifts: &[Shift]) -> HashMap<GuardID, [u32; 60]> {
+    let mut aggregates = HashMap::new();
+
+    for shift in shifts {
+        let freq_sleep_minutes = aggregates.entry(shift.guard_id).or_insert([0; 60]);
+        for &(asleep, wakeup) in &shift.naps {
+            for minute in asleep..wakeup {
+                freq_sleep_minutes[minute as usize] += 1;
+            }
+        }
+    }
+
     aggregates
-        .iter()
-        .map(|(guard_id, freq_sleep_minutes)| {
-            (guard_id, freq_sleep_minutes.iter().cloned().sum::<u32>())
-        })
-        .max_by(|(_, tot_minutes1), (_, tot_minutes2)| tot_minutes1.cmp(tot_minutes2))
-        .map(|(guard_id, _)| guard_id)
 }
 
-fn find_most_minute_sleep(freqs: &[u32; 60]) -> Option<usize> {
-    freqs
+fn render_sleep_chart(shifts: &[Shift]) -> String {
+    let id_width = shifts
         .iter()
-        .enumerate()
-        .max_by(|(_, freq_sleep1), (_, freq_sleep2)| freq_sleep1.cmp(freq_sleep2))
-        .map(|(minute, _)| minute)
+        .map(|shift| format!("#{} ", shift.guard_id).len())
+        .max()
+        .unwrap_or(0)
+        .max(6);
+    let prefix_width = "MM-DD  ".len() + id_width;
+    let mut chart = format!("{:<7}{:<width$}Minute\n", "Date", "ID", width = id_width);
+    let tens = (0..60).map(|minute| (b'0' + minute / 10) as char);
+    let ones = (0..60).map(|minute| (b'0' + minute % 10) as char);
+    chart.push_str(&format!(
+        "{:width$}{}\n",
+        "",
+        tens.collect::<String>(),
+        width = prefix_width
+    ));
+    chart.push_str(&format!(
+        "{:width$}{}\n",
+        "",
+        ones.collect::<String>(),
+        width = prefix_width
+    ));
+    for shift in shifts {
+        let mut minutes = [b'.'; 60];
+        for &(asleep, wakeup) in &shift.naps {
+            minutes[asleep as usize..wakeup as usize]
+                .iter_mut()
+                .for_each(|minute| *minute = b'#');
+        }
+        chart.push_str(&format!(
+            "{:02}-{:02}  {:<width$}{}\n",
+            shift.date.month,
+            shift.date.day,
+            format!("#{}", shift.guard_id),
+            String::from_utf8_lossy(&minutes),
+            width = id_width
+        ));
+    }
+    chart
 }
 
-fn find_most_sleep_minute_for_most_sleep_guard(
+struct GuardStats {
+    guard_id: GuardID,
+    total_minutes: u32,
+    most_slept_minute: Option<(usize, u32)>,
+    nights_worked: usize,
+}
+
+fn calculate_guard_stats(
+    shifts: &[Shift],
     aggregates: &HashMap<GuardID, [u32; 60]>,
-) -> Result<(GuardID, usize)> {
-    if let Some(guard_id) = find_most_sleep_guard(aggregates) {
-        if let Some(freqs) = aggregates.get(guard_id) {
-            if let Some(minute) = find_most_minute_sleep(freqs) {
-                Ok((*guard_id, minute))
-            } else {
-                Err(anyhow!("can't find most minute sleep"))
+) -> Vec<GuardStats> {
+    let mut stats = aggregates
+        .iter()
+        .map(|(&guard_id, freq_sleep_minutes)| GuardStats {
+            guard_id,
+            total_minutes: freq_sleep_minutes.iter().sum(),
+            most_slept_minute: find_most_minute_sleep(freq_sleep_minutes, TieBreak::Lowest)
+                .map(|best| (best.chosen[0], freq_sleep_minutes[best.chosen[0]]))
+                .filter(|(_, freq_sleep)| *freq_sleep > 0),
+            nights_worked: shifts
+                .iter()
+                .filter(|shift| shift.guard_id == guard_id)
+                .count(),
+        })
+        .collect::<Vec<_>>();
+    stats.sort_by_key(|stat| stat.guard_id);
+    stats
+}
+
+fn export_minutes_csv(aggregates: &HashMap<GuardID, [u32; 60]>) -> String {
+    let mut guard_ids = aggregates.keys().cloned().collect::<Vec<_>>();
+    guard_ids.sort_unstable();
+
+    let mut csv = String::from("guard_id");
+    (0..60).for_each(|minute| csv.push_str(&format!(",{}", minute)));
+    csv.push('\n');
+    for guard_id in guard_ids {
+        csv.push_str(&guard_id.to_string());
+        aggregates[&guard_id]
+            .iter()
+            .for_each(|freq_sleep| csv.push_str(&format!(",{}", freq_sleep)));
+        csv.push('\n');
+    }
+    csv
+}
+
+#[derive(Clone, Copy)]
+enum TieBreak {
+    Lowest,
+    Highest,
+    All,
+}
+
+impl FromStr for TieBreak {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lowest" => Ok(TieBreak::Lowest),
+            "highest" => Ok(TieBreak::Highest),
+            "all" => Ok(TieBreak::All),
+            _ => Err(anyhow!("unknown tie break policy: {}", s)),
+        }
+    }
+}
+
+struct Best<T> {
+    chosen: Vec<T>,
+    tied: bool,
+}
+
+fn select_best<T: Ord, K: Ord>(
+    candidates: impl Iterator<Item = (T, K)>,
+    tie_break: TieBreak,
+) -> Option<Best<T>> {
+    let mut best: Option<(K, Vec<T>)> = None;
+    for (candidate, key) in candidates {
+        match &mut best {
+            Some((best_key, tied)) if key == *best_key => tied.push(candidate),
+            Some((best_key, _)) if key < *best_key => {}
+            _ => best = Some((key, vec![candidate])),
+        }
+    }
+
+    best.map(|(_, mut tied)| {
+        tied.sort();
+        let is_tied = tied.len() > 1;
+        match tie_break {
+            TieBreak::Lowest => tied.truncate(1),
+            TieBreak::Highest => {
+                tied.drain(..tied.len() - 1);
             }
-        } else {
-            Err(anyhow!("can't find sleep freqs"))
+            TieBreak::All => {}
+        }
+        Best {
+            chosen: tied,
+            tied: is_tied,
         }
-    } else {
-        Err(anyhow!("can't find most sleepy guard"))
+    })
+}
+
+fn find_most_sleep_guard(
+    aggregates: &HashMap<GuardID, [u32; 60]>,
+    tie_break: TieBreak,
+) -> Option<Best<GuardID>> {
+    select_best(
+        aggregates.iter().map(|(guard_id, freq_sleep_minutes)| {
+            (*guard_id, freq_sleep_minutes.iter().cloned().sum::<u32>())
+        }),
+        tie_break,
+    )
+}
+
+fn find_most_minute_sleep(freqs: &[u32; 60], tie_break: TieBreak) -> Option<Best<usize>> {
+    select_best(freqs.iter().cloned().enumerate(), tie_break)
+}
+
+fn find_most_sleep_minute_for_most_sleep_guard(
+    aggregates: &HashMap<GuardID, [u32; 60]>,
+    tie_break: TieBreak,
+) -> Result<Best<(GuardID, usize)>> {
+    let guards = find_most_sleep_guard(aggregates, tie_break)
+        .ok_or_else(|| anyhow!("can't find most sleepy guard"))?;
+    let mut best = Best {
+        chosen: vec![],
+        tied: guards.tied,
+    };
+    for guard_id in guards.chosen {
+        let freqs = aggregates
+            .get(&guard_id)
+            .ok_or_else(|| anyhow!("can't find sleep freqs"))?;
+        let minutes = find_most_minute_sleep(freqs, tie_break)
+            .ok_or_else(|| anyhow!("can't find most minute sleep"))?;
+        best.tied |= minutes.tied;
+        best.chosen
+            .extend(minutes.chosen.into_iter().map(|minute| (guard_id, minute)));
     }
+
+    Ok(best)
 }
 
 fn find_most_sleep_minute_guard(
     aggregates: &HashMap<GuardID, [u32; 60]>,
-) -> Result<(GuardID, usize)> {
-    if let Some(((guard_id, minute), _)) = aggregates
-        .iter()
-        .flat_map(|(guard_id, freq_sleep_minutes)| {
-            freq_sleep_minutes
-                .iter()
-                .enumerate()
-                .max_by(|(_, freq_sleep1), (_, freq_sleep2)| freq_sleep1.cmp(freq_sleep2))
-                .map(|(minute, freq_sleep)| ((guard_id, minute), *freq_sleep))
-        })
-        .max_by(|(_, max_freq_sleep1), (_, max_freq_sleep2)| max_freq_sleep1.cmp(max_freq_sleep2))
-    {
-        Ok((*guard_id, minute))
-    } else {
-        Err(anyhow!("can't find max freq sleep by minutes",))
-    }
+    tie_break: TieBreak,
+) -> Result<Best<(GuardID, usize)>> {
+    select_best(
+        aggregates
+            .iter()
+            .flat_map(|(guard_id, freq_sleep_minutes)| {
+                freq_sleep_minutes
+                    .iter()
+                    .enumerate()
+                    .map(move |(minute, freq_sleep)| ((*guard_id, minute), *freq_sleep))
+            }),
+        tie_break,
+    )
+    .ok_or_else(|| anyhow!("can't find max freq sleep by minutes"))
 }
 
 fn main() -> Result<()> {
@@ -189,24 +473,59 @@ fn main() -> Result<()> {
         .filter_map(|line| line.ok().and_then(|s| s.parse::<GuardEvent>().ok()))
         .collect::<Vec<_>>();
     guard_events.sort_by(|ev1, ev2| ev1.datetime.cmp(&ev2.datetime));
-    let aggregates = aggregate_minutes_sleep_per_guard(&guard_events)?;
-
-    let (guard_id, minute) = find_most_sleep_minute_for_most_sleep_guard(&aggregates)?;
-    writeln!(
-        io::stdout(),
-        "most minute guard for most sleepy guard: guard_id x minute => {} x {} = {}",
-        guard_id,
-        minute,
-        guard_id * (minute as u32)
-    )?;
-    let (guard_id, minute) = find_most_sleep_minute_guard(&aggregates)?;
-    writeln!(
-        io::stdout(),
-        "most sleep minute guard: guard_id x minute => {} x {} = {}",
-        guard_id,
-        minute,
-        guard_id * (minute as u32)
-    )?;
+    let shifts = build_shifts(&guard_events)?;
+    let aggregates = aggregate_minutes_sleep_per_guard(&shifts);
+
+    let tie_break = match env::args().skip_while(|arg| arg != "--tie-break").nth(1) {
+        Some(policy) => policy.parse()?,
+        None => TieBreak::Lowest,
+    };
+
+    let best = find_most_sleep_minute_for_most_sleep_guard(&aggregates, tie_break)?;
+    for (guard_id, minute) in best.chosen {
+        writeln!(
+            io::stdout(),
+            "most minute guard for most sleepy guard: guard_id x minute => {} x {} = {}{}",
+            guard_id,
+            minute,
+            guard_id * (minute as u32),
+            if best.tied { " (tied)" } else { "" }
+        )?;
+    }
+    let best = find_most_sleep_minute_guard(&aggregates, tie_break)?;
+    for (guard_id, minute) in best.chosen {
+        writeln!(
+            io::stdout(),
+            "most sleep minute guard: guard_id x minute => {} x {} = {}{}",
+            guard_id,
+            minute,
+            guard_id * (minute as u32),
+            if best.tied { " (tied)" } else { "" }
+        )?;
+    }
+
+    if env::args().any(|arg| arg == "--chart") {
+        write!(io::stdout(), "{}", render_sleep_chart(&shifts))?;
+    }
+    if env::args().any(|arg| arg == "--stats") {
+        for stat in calculate_guard_stats(&shifts, &aggregates) {
+            let most_slept = match stat.most_slept_minute {
+                Some((minute, freq_sleep)) => format!("minute {} ({} times)", minute, freq_sleep),
+                None => "never asleep".to_string(),
+            };
+            writeln!(
+                io::stdout(),
+                "guard #{}: {} minutes asleep over {} nights, most slept {}",
+                stat.guard_id,
+                stat.total_minutes,
+                stat.nights_worked,
+                most_slept
+            )?;
+        }
+    }
+    if let Some(path) = env::args().skip_while(|arg| arg != "--csv").nth(1) {
+        fs::write(&path, export_minutes_csv(&aggregates)).context("failed to write csv")?;
+    }
 
     Ok(())
 }
@@ -1,74 +1,391 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Error, Result};
+use rayon::prelude::*;
 use std::{
+    collections::HashSet,
+    env,
     fs::File,
-    io::{self, BufRead, BufReader, Write},
+    hash::Hash,
+    io::{self, BufRead, BufReader, Read, Write},
+    str::FromStr,
 };
 
-fn reacting(mut polymer: impl Iterator<Item = u8>) -> String {
-    fn test_react(unit1: u8, unit2: u8) -> bool {
-        if unit1 < unit2 {
-            unit2 - unit1 == 32
-        } else {
-            unit1 - unit2 == 32
+trait Reaction<U> {
+    fn reacts(&self, unit1: &U, unit2: &U) -> bool;
+}
+
+impl<U, F: Fn(&U, &U) -> bool> Reaction<U> for F {
+    fn reacts(&self, unit1: &U, unit2: &U) -> bool {
+        self(unit1, unit2)
+    }
+}
+
+struct AsciiCasePair;
+
+impl Reaction<u8> for AsciiCasePair {
+    fn reacts(&self, unit1: &u8, unit2: &u8) -> bool {
+        unit1 != unit2 && unit1.eq_ignore_ascii_case(unit2)
+    }
+}
+
+struct UnicodeCaseFold;
+
+impl Reaction<char> for UnicodeCaseFold {
+    fn reacts(&self, unit1: &char, unit2: &char) -> bool {
+        unit1 != unit2 && unit1.to_lowercase().eq(unit2.to_lowercase())
+    }
+}
+
+struct RuleTable<U> {
+    pairs: HashSet<(U, U)>,
+}
+
+impl<U: Eq + Hash + Clone> RuleTable<U> {
+    fn new(pairs: impl IntoIterator<Item = (U, U)>) -> Self {
+        let pairs = pairs
+            .into_iter()
+            .flat_map(|(unit1, unit2)| vec![(unit1.clone(), unit2.clone()), (unit2, unit1)])
+            .collect();
+        RuleTable { pairs }
+    }
+}
+
+impl<U: Eq + Hash + Clone + Ord> RuleTable<U> {
+    fn unit_classes(&self) -> Vec<Vec<U>> {
+        let mut classes: Vec<Vec<U>> = Vec::new();
+        for (unit1, unit2) in &self.pairs {
+            let mut merged = vec![unit1.clone(), unit2.clone()];
+            classes.retain(|class| {
+                if class.contains(unit1) || class.contains(unit2) {
+                    merged.extend(class.iter().cloned());
+                    false
+                } else {
+                    true
+                }
+            });
+            merged.sort();
+            merged.dedup();
+            classes.push(merged);
         }
+        classes.sort();
+        classes
+    }
+}
+
+impl<U: Eq + Hash + Clone> Reaction<U> for RuleTable<U> {
+    fn reacts(&self, unit1: &U, unit2: &U) -> bool {
+        self.pairs.contains(&(unit1.clone(), unit2.clone()))
     }
+}
 
+impl FromStr for RuleTable<u8> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pairs = s
+            .split(',')
+            .map(|pair| match pair.trim().as_bytes() {
+                [unit1, unit2] => Ok((*unit1, *unit2)),
+                _ => Err(anyhow!("invalid reaction pair: {}", pair)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RuleTable::new(pairs))
+    }
+}
+
+fn react_unit<U, R: Reaction<U>>(new_polymer: &mut Vec<U>, unit: U, reaction: &R) {
+    match new_polymer.last() {
+        Some(last) if reaction.reacts(last, &unit) => {
+            new_polymer.pop();
+        }
+        _ => new_polymer.push(unit),
+    }
+}
+
+fn reacting<U, R: Reaction<U>>(polymer: impl Iterator<Item = U>, reaction: &R) -> Vec<U> {
+    let mut new_polymer: Vec<U> = Vec::new();
+
+    for unit in polymer {
+        react_unit(&mut new_polymer, unit, reaction);
+    }
+
+    new_polymer
+}
+
+const PROGRESS_INTERVAL: u64 = 1 << 26;
+
+fn reacting_stream<R: Reaction<u8>>(
+    mut reader: impl BufRead,
+    reaction: &R,
+    mut progress: impl FnMut(u64, usize) -> io::Result<()>,
+) -> Result<(Vec<u8>, u64)> {
     let mut new_polymer = Vec::new();
-    let mut reactant = polymer.next();
+    let mut consumed = 0u64;
+    let mut next_report = PROGRESS_INTERVAL;
 
-    for unit2 in polymer {
-        if let Some(unit1) = reactant {
-            if test_react(unit1, unit2) {
-                reactant = new_polymer.pop();
+    loop {
+        let buf = reader.fill_buf().context("failed to read polymer")?;
+        if buf.is_empty() {
+            break;
+        }
+        let line_end = buf.iter().position(|&b| b == b'\n' || b == b'\r');
+        let chunk = &buf[..line_end.unwrap_or(buf.len())];
+        for &unit in chunk {
+            react_unit(&mut new_polymer, unit, reaction);
+        }
+        let len = chunk.len();
+        reader.consume(len);
+
+        consumed += len as u64;
+        if consumed >= next_report {
+            progress(consumed, new_polymer.len()).context("failed to report progress")?;
+            next_report = consumed + PROGRESS_INTERVAL;
+        }
+        if line_end.is_some() {
+            break;
+        }
+    }
+    progress(consumed, new_polymer.len()).context("failed to report progress")?;
+
+    Ok((new_polymer, consumed))
+}
+
+struct GeneratedPolymer {
+    remaining: u64,
+    state: u64,
+}
+
+impl GeneratedPolymer {
+    fn new(size: u64) -> Self {
+        GeneratedPolymer {
+            remaining: size,
+            state: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+}
+
+impl Read for GeneratedPolymer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.remaining as usize);
+        for unit in &mut buf[..len] {
+            self.state ^= self.state << 13;
+            self.state ^= self.state >> 7;
+            self.state ^= self.state << 17;
+            let letter = b'a' + (self.state % 4) as u8;
+            *unit = if (self.state >> 8) & 1 == 0 {
+                letter
+            } else {
+                letter.to_ascii_uppercase()
+            };
+        }
+        self.remaining -= len as u64;
+        Ok(len)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum UnitFate {
+    Survived,
+    Reacted { partner: usize, depth: usize },
+}
+
+fn reacting_with_trace<U, R: Reaction<U>>(
+    polymer: impl Iterator<Item = U>,
+    reaction: &R,
+) -> (Vec<U>, Vec<UnitFate>) {
+    let mut new_polymer: Vec<(usize, U)> = Vec::new();
+    let mut trace = Vec::new();
+
+    for (idx, unit) in polymer.enumerate() {
+        trace.push(UnitFate::Survived);
+        match new_polymer.last() {
+            Some((_, last)) if reaction.reacts(last, &unit) => {
+                let (partner, _) = new_polymer.pop().unwrap();
+                trace[partner] = UnitFate::Reacted {
+                    partner: idx,
+                    depth: 0,
+                };
+                trace[idx] = UnitFate::Reacted { partner, depth: 0 };
+            }
+            _ => new_polymer.push((idx, unit)),
+        }
+    }
+
+    let mut open = 0;
+    for (idx, fate) in trace.iter_mut().enumerate() {
+        if let UnitFate::Reacted { partner, depth } = fate {
+            if *partner > idx {
+                *depth = open;
+                open += 1;
             } else {
-                new_polymer.push(unit1);
-                reactant = Some(unit2);
+                open -= 1;
+                *depth = open;
             }
-        } else {
-            reactant = Some(unit2);
         }
     }
-    if let Some(last) = reactant {
-        new_polymer.push(last);
+
+    (
+        new_polymer.into_iter().map(|(_, unit)| unit).collect(),
+        trace,
+    )
+}
+
+fn render_trace(polymer: &[u8], trace: &[UnitFate]) -> String {
+    let mut rendered = String::with_capacity(2 * polymer.len());
+    for (idx, (unit, fate)) in polymer.iter().zip(trace).enumerate() {
+        match fate {
+            UnitFate::Reacted { partner, .. } if *partner > idx => {
+                rendered.push('(');
+                rendered.push(*unit as char);
+            }
+            UnitFate::Reacted { .. } => {
+                rendered.push(*unit as char);
+                rendered.push(')');
+            }
+            UnitFate::Survived => rendered.push(*unit as char),
+        }
     }
+    rendered
+}
+
+fn ascii_unit_classes() -> Vec<Vec<u8>> {
+    (b'A'..=b'Z')
+        .map(|b| vec![b, b.to_ascii_lowercase()])
+        .collect()
+}
 
-    String::from_utf8_lossy(&new_polymer).to_string()
+fn rank_unit_removals<'c, U, R>(
+    reduced_polymer: &[U],
+    unit_classes: &'c [Vec<U>],
+    reaction: &R,
+) -> Vec<(&'c [U], usize)>
+where
+    U: PartialEq + Clone + Send + Sync,
+    R: Reaction<U> + Sync,
+{
+    let mut ranking = unit_classes
+        .par_iter()
+        .enumerate()
+        .map(|(idx, class)| {
+            let reduce_polymer = reduced_polymer
+                .iter()
+                .filter(|unit| !class.contains(unit))
+                .cloned();
+            (idx, reacting(reduce_polymer, reaction).len())
+        })
+        .collect::<Vec<_>>();
+    ranking.sort_by_key(|&(idx, len)| (len, idx));
+    ranking
+        .into_iter()
+        .map(|(idx, len)| (unit_classes[idx].as_slice(), len))
+        .collect()
 }
 
 fn main() -> Result<()> {
     let file = File::open("2018/day-05/input/input.txt").context("failed to read input file")?;
     let reader = BufReader::new(file);
 
-    if let Some(Ok(base_polymer)) = reader.lines().take(1).next() {
-        let base_polymer = base_polymer.as_bytes();
-        let shrink_polymer = reacting(base_polymer.iter().cloned());
+    let (shrink_polymer, consumed) = reacting_stream(reader, &AsciiCasePair, |_, _| Ok(()))?;
+    if consumed == 0 {
+        writeln!(io::stderr(), "no polymer to be processed")?;
+        return Ok(());
+    }
+    writeln!(
+        io::stdout(),
+        "shrink polymer has {} unit",
+        shrink_polymer.len()
+    )?;
+
+    let unit_classes = ascii_unit_classes();
+    let ranking = rank_unit_removals(&shrink_polymer, &unit_classes, &AsciiCasePair);
+    if let Some((class, len)) = ranking.first() {
         writeln!(
             io::stdout(),
-            "shrink polymer has {} unit",
-            shrink_polymer.len()
+            "by reducing unit {}, polymer can further be shrink to {} unit",
+            class[0] as char,
+            len
         )?;
+        if env::args().any(|arg| arg == "--ranking") {
+            for (class, len) in &ranking {
+                writeln!(io::stdout(), "{}: {}", class[0] as char, len)?;
+            }
+        }
+    } else {
+        writeln!(io::stderr(), "can't reduce further")?;
+    }
+
+    let needs_base_polymer =
+        env::args().any(|arg| ["--trace", "--rules", "--unicode"].contains(&arg.as_str()));
+    if needs_base_polymer {
+        let file =
+            File::open("2018/day-05/input/input.txt").context("failed to read input file")?;
+        let reader = BufReader::new(file);
 
-        if let Some((b, len)) = (b'A'..=b'Z')
-            .map(|b| {
-                let reduce_polymer = base_polymer
+        if let Some(Ok(base_polymer)) = reader.lines().take(1).next() {
+            let base_polymer = base_polymer.as_bytes();
+            if env::args().any(|arg| arg == "--trace") {
+                let (_, trace) = reacting_with_trace(base_polymer.iter().cloned(), &AsciiCasePair);
+                let max_depth = trace
                     .iter()
-                    .cloned()
-                    .filter(|&v| v != b && v != (b + 32));
-                (b, reacting(reduce_polymer).len())
-            })
-            .min_by(|(_, len1), (_, len2)| len1.cmp(len2))
-        {
+                    .filter_map(|fate| match fate {
+                        UnitFate::Reacted { depth, .. } => Some(*depth),
+                        UnitFate::Survived => None,
+                    })
+                    .max();
+                writeln!(io::stdout(), "{}", render_trace(base_polymer, &trace))?;
+                if let Some(max_depth) = max_depth {
+                    writeln!(io::stdout(), "deepest reaction nesting: {}", max_depth)?;
+                }
+            }
+
+            if let Some(rules) = env::args().skip_while(|arg| arg != "--rules").nth(1) {
+                let rule_table = rules.parse::<RuleTable<u8>>()?;
+                let shrink_polymer = reacting(base_polymer.iter().cloned(), &rule_table);
+                writeln!(
+                    io::stdout(),
+                    "with reaction rules {}, polymer shrinks to {} unit",
+                    rules,
+                    shrink_polymer.len()
+                )?;
+                let unit_classes = rule_table.unit_classes();
+                let ranking = rank_unit_removals(&shrink_polymer, &unit_classes, &rule_table);
+                if let Some((class, len)) = ranking.first() {
+                    writeln!(
+                        io::stdout(),
+                        "by reducing units {}, polymer can further be shrink to {} unit",
+                        String::from_utf8_lossy(class),
+                        len
+                    )?;
+                }
+            }
+            if env::args().any(|arg| arg == "--unicode") {
+                let base_polymer = String::from_utf8_lossy(base_polymer);
+                writeln!(
+                    io::stdout(),
+                    "with unicode case folding, polymer shrinks to {} unit",
+                    reacting(base_polymer.chars(), &UnicodeCaseFold).len()
+                )?;
+            }
+        }
+    }
+
+    if let Some(size) = env::args().skip_while(|arg| arg != "--generate").nth(1) {
+        let size = size.parse().context("invalid polymer size")?;
+        let reader = BufReader::with_capacity(1 << 20, GeneratedPolymer::new(size));
+        let (shrink_polymer, _) = reacting_stream(reader, &AsciiCasePair, |consumed, len| {
             writeln!(
-                io::stdout(),
-                "by reducing unit {}, polymer can further be shrink to {} unit",
-                b as char,
+                io::stderr(),
+                "reacted {} of {} unit, {} unit survive",
+                consumed,
+                size,
                 len
-            )?;
-        } else {
-            writeln!(io::stderr(), "can't reduce further")?;
-        }
-    } else {
-        writeln!(io::stderr(), "no polymer to be processed")?;
+            )
+        })?;
+        writeln!(
+            io::stdout(),
+            "generated polymer of {} unit shrinks to {} unit",
+            size,
+            shrink_polymer.len()
+        )?;
     }
 
     Ok(())
@@ -1,8 +1,7 @@
 use anyhow::{anyhow, Context, Error, Result};
 use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    hash::Hash,
+    env,
+    fs::{self, File},
     io::{self, BufRead, BufReader, Write},
     str::FromStr,
 };
@@ -45,14 +44,7 @@ impl Coordinate {
     }
 
     fn calculate_manhattan_length(&self, other: &Coordinate) -> u32 {
-        ((self.x - other.x).abs() as u32) + ((self.y - other.y).abs() as u32)
-    }
-
-    fn calculate_longest_manhattant_distant(&self, coordinates: &[Coordinate]) -> Option<u32> {
-        coordinates
-            .iter()
-            .map(|coordinate| self.calculate_manhattan_length(coordinate))
-            .max()
+        (self.x - other.x).unsigned_abs() + (self.y - other.y).unsigned_abs()
     }
 
     fn calculate_coordinates_within_manhattan_radius(&self, radius: u32) -> Vec<Coordinate> {
@@ -100,88 +92,410 @@ impl Coordinate {
     }
 }
 
-fn calculate_largest_areas_nearest_to_one_coordinate_only(
-    coordinates: &[Coordinate],
-    center: &Coordinate,
-) -> Option<u32> {
-    let farthest_distant = center
-        .calculate_longest_manhattant_distant(&coordinates)
-        .unwrap();
-    let mut coordinates_counter = HashMap::new();
-
-    for i in 0..=farthest_distant {
-        center
-            .calculate_coordinates_within_manhattan_radius(i)
-            .iter()
-            .for_each(|point| {
-                let mut coordinates_distant = coordinates
+trait Metric {
+    fn distance(&self, from: &Coordinate, to: &Coordinate) -> u64;
+
+    fn ring(&self, center: &Coordinate, radius: u32) -> Vec<Coordinate>;
+
+    fn infinite_regions(&self, coordinates: &[Coordinate]) -> Vec<bool>;
+}
+
+struct Manhattan;
+
+impl Metric for Manhattan {
+    fn distance(&self, from: &Coordinate, to: &Coordinate) -> u64 {
+        from.calculate_manhattan_length(to) as u64
+    }
+
+    fn ring(&self, center: &Coordinate, radius: u32) -> Vec<Coordinate> {
+        center.calculate_coordinates_within_manhattan_radius(radius)
+    }
+
+    fn infinite_regions(&self, coordinates: &[Coordinate]) -> Vec<bool> {
+        let mut infinite = vec![false; coordinates.len()];
+        if let Some(bbox) = BoundingBox::new(coordinates) {
+            for point in bbox.points().filter(|point| bbox.is_edge(point)) {
+                if let Some(idx) = find_nearest_coordinate(&point, coordinates, self) {
+                    infinite[idx] = true;
+                }
+            }
+        }
+        infinite
+    }
+}
+
+struct Chebyshev;
+
+impl Metric for Chebyshev {
+    fn distance(&self, from: &Coordinate, to: &Coordinate) -> u64 {
+        (from.x - to.x)
+            .unsigned_abs()
+            .max((from.y - to.y).unsigned_abs()) as u64
+    }
+
+    fn ring(&self, center: &Coordinate, radius: u32) -> Vec<Coordinate> {
+        let radius = radius as i32;
+        if radius == 0 {
+            return vec![center.clone()];
+        }
+        let mut list = Vec::with_capacity((8 * radius) as usize);
+        for i in -radius..radius {
+            list.push(Coordinate {
+                x: center.x + i,
+                y: center.y - radius,
+            });
+            list.push(Coordinate {
+                x: center.x + radius,
+                y: center.y + i,
+            });
+            list.push(Coordinate {
+                x: center.x - i,
+                y: center.y + radius,
+            });
+            list.push(Coordinate {
+                x: center.x - radius,
+                y: center.y - i,
+            });
+        }
+
+        list
+    }
+
+    fn infinite_regions(&self, coordinates: &[Coordinate]) -> Vec<bool> {
+        let mut infinite = vec![false; coordinates.len()];
+        for (flip_x, flip_y) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let flipped = coordinates
+                .iter()
+                .map(|c| (c.x * flip_x, c.y * flip_y))
+                .collect::<Vec<_>>();
+            let min_offset = flipped.iter().map(|(x, y)| x - y).min().unwrap_or(0);
+            let max_offset = flipped.iter().map(|(x, y)| x - y).max().unwrap_or(0);
+            for offset in min_offset - 1..=max_offset + 1 {
+                let reach = flipped
                     .iter()
-                    .map(|coordinate| {
-                        (
-                            coordinate.clone(),
-                            point.calculate_manhattan_length(coordinate),
-                        )
-                    })
+                    .map(|(x, y)| (x - offset).min(*y))
                     .collect::<Vec<_>>();
-                coordinates_distant.sort_by(|(_, dist1), (_, dist2)| dist1.cmp(dist2));
-                if coordinates_distant[0].1 < coordinates_distant[1].1 {
-                    *coordinates_counter
-                        .entry(coordinates_distant[0].0.clone())
-                        .or_insert(0u32) += 1;
+                let best = reach.iter().max();
+                let mut farthest = reach.iter().enumerate().filter(|(_, r)| Some(*r) == best);
+                if let (Some((idx, _)), None) = (farthest.next(), farthest.next()) {
+                    infinite[idx] = true;
+                }
+            }
+        }
+        infinite
+    }
+}
+
+struct SquaredEuclidean;
+
+impl Metric for SquaredEuclidean {
+    fn distance(&self, from: &Coordinate, to: &Coordinate) -> u64 {
+        let dx = (from.x - to.x).unsigned_abs() as u64;
+        let dy = (from.y - to.y).unsigned_abs() as u64;
+        dx * dx + dy * dy
+    }
+
+    fn ring(&self, center: &Coordinate, radius: u32) -> Vec<Coordinate> {
+        let inner = (radius as u64).pow(2);
+        let outer = (radius as u64 + 1).pow(2);
+        let mut list = Vec::new();
+        for dx in -(radius as i64)..=radius as i64 {
+            let dx2 = (dx * dx) as u64;
+            let mut dy_min = (inner.saturating_sub(dx2) as f64).sqrt() as u64;
+            while dy_min * dy_min + dx2 < inner {
+                dy_min += 1;
+            }
+            let mut dy = dy_min;
+            while dy * dy + dx2 < outer {
+                list.push(Coordinate {
+                    x: center.x + dx as i32,
+                    y: center.y + dy as i32,
+                });
+                if dy > 0 {
+                    list.push(Coordinate {
+                        x: center.x + dx as i32,
+                        y: center.y - dy as i32,
+                    });
                 }
+                dy += 1;
+            }
+        }
+
+        list
+    }
+
+    fn infinite_regions(&self, coordinates: &[Coordinate]) -> Vec<bool> {
+        let cross = |origin: &Coordinate, a: &Coordinate, b: &Coordinate| {
+            (a.x - origin.x) as i64 * (b.y - origin.y) as i64
+                - (a.y - origin.y) as i64 * (b.x - origin.x) as i64
+        };
+        coordinates
+            .iter()
+            .map(|coordinate| {
+                let mut others = coordinates.iter().filter(|other| *other != coordinate);
+                let mut any_other = false;
+                let on_hull = others.any(|other| {
+                    any_other = true;
+                    coordinates.iter().all(|c| cross(coordinate, other, c) >= 0)
+                        || coordinates.iter().all(|c| cross(coordinate, other, c) <= 0)
+                });
+                on_hull || !any_other
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Box<dyn Metric> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manhattan" => Ok(Box::new(Manhattan)),
+            "chebyshev" => Ok(Box::new(Chebyshev)),
+            "euclidean" => Ok(Box::new(SquaredEuclidean)),
+            _ => Err(anyhow!("unknown metric: {}", s)),
+        }
+    }
+}
+
+struct BoundingBox {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+impl BoundingBox {
+    fn new(coordinates: &[Coordinate]) -> Option<Self> {
+        Some(BoundingBox {
+            min_x: coordinates.iter().map(|c| c.x).min()?,
+            min_y: coordinates.iter().map(|c| c.y).min()?,
+            max_x: coordinates.iter().map(|c| c.x).max()?,
+            max_y: coordinates.iter().map(|c| c.y).max()?,
+        })
+    }
+
+    fn expand(&self, margin: i32) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+
+    fn width(&self) -> usize {
+        (self.max_x - self.min_x + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.max_y - self.min_y + 1) as usize
+    }
+
+    fn points(&self) -> impl Iterator<Item = Coordinate> + '_ {
+        (self.min_y..=self.max_y)
+            .flat_map(move |y| (self.min_x..=self.max_x).map(move |x| Coordinate { x, y }))
+    }
+
+    fn is_edge(&self, point: &Coordinate) -> bool {
+        point.x == self.min_x
+            || point.x == self.max_x
+            || point.y == self.min_y
+            || point.y == self.max_y
+    }
+}
+
+struct Region {
+    coordinate: usize,
+    size: u32,
+    infinite: bool,
+}
+
+struct NearestMap {
+    bbox: BoundingBox,
+    labels: Vec<Option<usize>>,
+    infinite: Vec<bool>,
+}
+
+fn find_nearest_coordinate<M: Metric + ?Sized>(
+    point: &Coordinate,
+    coordinates: &[Coordinate],
+    metric: &M,
+) -> Option<usize> {
+    let mut nearest = None;
+    let mut nearest_distance = u64::MAX;
+    for (idx, coordinate) in coordinates.iter().enumerate() {
+        let distance = metric.distance(point, coordinate);
+        if distance < nearest_distance {
+            nearest = Some(idx);
+            nearest_distance = distance;
+        } else if distance == nearest_distance {
+            nearest = None;
+        }
+    }
+    nearest
+}
+
+impl NearestMap {
+    fn new<M: Metric + ?Sized>(coordinates: &[Coordinate], metric: &M) -> Option<Self> {
+        let infinite = metric.infinite_regions(coordinates);
+        let base = BoundingBox::new(coordinates)?;
+        let mut margin = 0;
+        loop {
+            let bbox = base.expand(margin);
+            let labels = bbox
+                .points()
+                .map(|point| find_nearest_coordinate(&point, coordinates, metric))
+                .collect::<Vec<_>>();
+            let finite_on_edge = bbox.points().zip(&labels).any(|(point, label)| {
+                matches!(label, Some(idx) if !infinite[*idx]) && bbox.is_edge(&point)
             });
+            if !finite_on_edge {
+                return Some(NearestMap {
+                    bbox,
+                    labels,
+                    infinite,
+                });
+            }
+            margin = (margin * 2).max(1);
+        }
     }
 
-    let mut infinite_points = HashSet::new();
-    center
-        .calculate_coordinates_within_manhattan_radius(farthest_distant)
-        .iter()
-        .for_each(|point| {
-            let mut coordinates_distant = coordinates
+    fn regions(&self, coordinates: &[Coordinate]) -> (Vec<Region>, u32) {
+        let mut regions = (0..coordinates.len())
+            .map(|coordinate| Region {
+                coordinate,
+                size: 0,
+                infinite: self.infinite[coordinate],
+            })
+            .collect::<Vec<_>>();
+        let mut ties = 0;
+        for label in &self.labels {
+            match label {
+                Some(idx) => regions[*idx].size += 1,
+                None => ties += 1,
+            }
+        }
+
+        (regions, ties)
+    }
+}
+
+fn calculate_safe_region<M: Metric + ?Sized>(
+    bbox: &BoundingBox,
+    coordinates: &[Coordinate],
+    max_total_acceptable_distance: u64,
+    metric: &M,
+) -> Vec<bool> {
+    bbox.points()
+        .map(|point| {
+            coordinates
                 .iter()
-                .map(|coordinate| {
-                    (
-                        coordinate.clone(),
-                        point.calculate_manhattan_length(coordinate),
-                    )
-                })
-                .collect::<Vec<_>>();
-            coordinates_distant.sort_by(|(_, dist1), (_, dist2)| dist1.cmp(dist2));
-            if coordinates_distant[0].1 < coordinates_distant[1].1 {
-                infinite_points.insert(coordinates_distant[0].0.clone());
+                .map(|coordinate| metric.distance(&point, coordinate))
+                .sum::<u64>()
+                < max_total_acceptable_distance
+        })
+        .collect()
+}
+
+fn region_color(idx: usize, infinite: bool) -> (u8, u8, u8) {
+    let hue = (idx * 137) % 360;
+    let value = if infinite { 110.0 } else { 230.0 };
+    let chroma = value * 0.7;
+    let x = chroma * (1.0 - (((hue as f64 / 60.0) % 2.0) - 1.0).abs());
+    let (r, g, b) = match hue / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    ((r + m) as u8, (g + m) as u8, (b + m) as u8)
+}
+
+impl NearestMap {
+    fn render_text(&self, coordinates: &[Coordinate], safe_region: Option<&[bool]>) -> String {
+        let (regions, _) = self.regions(coordinates);
+        let mut rendered = String::with_capacity((self.bbox.width() + 1) * self.bbox.height());
+        for (idx, (point, label)) in self.bbox.points().zip(&self.labels).enumerate() {
+            let symbol = match label {
+                Some(owner) if coordinates[*owner] == point => (b'A' + (*owner % 26) as u8) as char,
+                _ if safe_region.is_some_and(|safe| safe[idx]) => '#',
+                Some(owner) => (b'a' + (*owner % 26) as u8) as char,
+                None => '.',
+            };
+            rendered.push(symbol);
+            if point.x == self.bbox.max_x {
+                rendered.push('\n');
             }
-        });
+        }
+        let infinite = regions
+            .iter()
+            .filter(|region| region.infinite)
+            .map(|region| ((b'A' + (region.coordinate % 26) as u8) as char).to_string())
+            .collect::<Vec<_>>();
+        if !infinite.is_empty() {
+            rendered.push_str(&format!("infinite regions: {}\n", infinite.join(", ")));
+        }
+        rendered
+    }
+
+    fn render_ppm(&self, coordinates: &[Coordinate], safe_region: Option<&[bool]>) -> Vec<u8> {
+        let (regions, _) = self.regions(coordinates);
+        let mut image =
+            format!("P6\n{} {}\n255\n", self.bbox.width(), self.bbox.height()).into_bytes();
+        for (idx, (point, label)) in self.bbox.points().zip(&self.labels).enumerate() {
+            let (r, g, b) = match label {
+                Some(owner) if coordinates[*owner] == point => (255, 255, 255),
+                Some(owner) => region_color(*owner, regions[*owner].infinite),
+                None => (0, 0, 0),
+            };
+            if safe_region.is_some_and(|safe| safe[idx]) {
+                image.extend_from_slice(&[r / 2 + 127, g / 2 + 127, b / 2 + 127]);
+            } else {
+                image.extend_from_slice(&[r, g, b]);
+            }
+        }
+        image
+    }
+}
 
-    coordinates_counter
+fn calculate_largest_areas_nearest_to_one_coordinate_only<M: Metric + ?Sized>(
+    coordinates: &[Coordinate],
+    metric: &M,
+) -> Option<u32> {
+    let nearest_map = NearestMap::new(coordinates, metric)?;
+    let (regions, _) = nearest_map.regions(coordinates);
+    regions
         .iter()
-        .filter(|(coordinate, _)| !infinite_points.contains(coordinate))
-        .map(|(_, size)| *size)
+        .filter(|region| !region.infinite)
+        .map(|region| region.size)
         .max()
 }
 
-fn calculate_largest_areas_nearest_to_all_coordinates(
+fn calculate_largest_areas_nearest_to_all_coordinates<M: Metric + ?Sized>(
     coordinates: &[Coordinate],
     center: &Coordinate,
-    max_total_acceptable_distance: u32,
+    max_total_acceptable_distance: u64,
+    metric: &M,
 ) -> u32 {
     let mut areas = 0;
 
     let mut radius = 0;
     loop {
         let mut any_within_acceptable_distance = false;
-        center
-            .calculate_coordinates_within_manhattan_radius(radius)
-            .iter()
-            .for_each(|point| {
-                let total_distance: u32 = coordinates
-                    .iter()
-                    .map(|coordinate| point.calculate_manhattan_length(coordinate))
-                    .sum();
-                if total_distance < max_total_acceptable_distance {
-                    any_within_acceptable_distance = true;
-                    areas += 1;
-                }
-            });
+        metric.ring(center, radius).iter().for_each(|point| {
+            let total_distance: u64 = coordinates
+                .iter()
+                .map(|coordinate| metric.distance(point, coordinate))
+                .sum();
+            if total_distance < max_total_acceptable_distance {
+                any_within_acceptable_distance = true;
+                areas += 1;
+            }
+        });
         radius += 1;
         if !any_within_acceptable_distance {
             break;
@@ -191,6 +505,60 @@ fn calculate_largest_areas_nearest_to_all_coordinates(
     areas
 }
 
+fn calculate_axis_distance_sums(values: &[i32], max_threshold: u64) -> Vec<u64> {
+    let mut values = values.iter().map(|&v| v as i64).collect::<Vec<_>>();
+    values.sort_unstable();
+    let n = values.len() as i64;
+    let total = values.iter().sum::<i64>();
+    let mut prefix_sums = Vec::with_capacity(values.len() + 1);
+    prefix_sums.push(0i64);
+    for v in &values {
+        prefix_sums.push(prefix_sums.last().unwrap() + v);
+    }
+
+    let reach = max_threshold as i64 / n + 1;
+    let mut sums = (values[0] - reach..=values[values.len() - 1] + reach)
+        .map(|pos| {
+            let k = values.partition_point(|&v| v <= pos);
+            let below = pos * k as i64 - prefix_sums[k];
+            let above = (total - prefix_sums[k]) - pos * (n - k as i64);
+            (below + above) as u64
+        })
+        .filter(|&sum| sum < max_threshold)
+        .collect::<Vec<_>>();
+    sums.sort_unstable();
+    sums
+}
+
+fn calculate_safe_region_areas(coordinates: &[Coordinate], thresholds: &[u64]) -> Vec<u64> {
+    let max_threshold = match thresholds.iter().max() {
+        Some(&max_threshold) if !coordinates.is_empty() => max_threshold,
+        _ => return vec![0; thresholds.len()],
+    };
+    let xs = coordinates.iter().map(|c| c.x).collect::<Vec<_>>();
+    let ys = coordinates.iter().map(|c| c.y).collect::<Vec<_>>();
+    let x_sums = calculate_axis_distance_sums(&xs, max_threshold);
+    let y_sums = calculate_axis_distance_sums(&ys, max_threshold);
+
+    thresholds
+        .iter()
+        .map(|&threshold| {
+            let mut areas = 0;
+            let mut y_end = y_sums.len();
+            for &x_sum in &x_sums {
+                if x_sum >= threshold {
+                    break;
+                }
+                while y_end > 0 && x_sum + y_sums[y_end - 1] >= threshold {
+                    y_end -= 1;
+                }
+                areas += y_end as u64;
+            }
+            areas
+        })
+        .collect()
+}
+
 fn main() -> Result<()> {
     let file = File::open("2018/day-06/input/input.txt").context("failed to read input file")?;
     let reader = BufReader::new(file);
@@ -200,14 +568,96 @@ fn main() -> Result<()> {
         .filter_map(|line| line.ok().and_then(|s| s.parse::<Coordinate>().ok()))
         .collect::<Vec<_>>();
     let center = Coordinate::find_center(&coordinates);
+    let metric_name = env::args()
+        .skip_while(|arg| arg != "--metric")
+        .nth(1)
+        .unwrap_or_else(|| "manhattan".to_string());
+    let metric = metric_name.parse::<Box<dyn Metric>>()?;
 
     if let Some(max_size) =
-        calculate_largest_areas_nearest_to_one_coordinate_only(&coordinates, &center)
+        calculate_largest_areas_nearest_to_one_coordinate_only(&coordinates, metric.as_ref())
     {
         writeln!(io::stdout(), "max coverage: {}", max_size)?;
     }
-    let areas = calculate_largest_areas_nearest_to_all_coordinates(&coordinates, &center, 10_000);
+    if env::args().any(|arg| arg == "--regions") {
+        if let Some(nearest_map) = NearestMap::new(&coordinates, metric.as_ref()) {
+            let (regions, ties) = nearest_map.regions(&coordinates);
+            for region in regions {
+                let coordinate = &coordinates[region.coordinate];
+                writeln!(
+                    io::stdout(),
+                    "{}, {}: {}{}",
+                    coordinate.x,
+                    coordinate.y,
+                    region.size,
+                    if region.infinite { " (infinite)" } else { "" }
+                )?;
+            }
+            writeln!(
+                io::stdout(),
+                "tied points within {}x{} box: {}",
+                nearest_map.bbox.width(),
+                nearest_map.bbox.height(),
+                ties
+            )?;
+        }
+    }
+    let threshold = match env::args().skip_while(|arg| arg != "--threshold").nth(1) {
+        Some(threshold) => threshold.parse().context("invalid threshold")?,
+        None => 10_000,
+    };
+    let render = env::args().any(|arg| arg == "--render");
+    let image_path = env::args().skip_while(|arg| arg != "--image").nth(1);
+    if render || image_path.is_some() {
+        if let Some(nearest_map) = NearestMap::new(&coordinates, metric.as_ref()) {
+            let safe_region = if env::args().any(|arg| arg == "--overlay") {
+                Some(calculate_safe_region(
+                    &nearest_map.bbox,
+                    &coordinates,
+                    threshold,
+                    metric.as_ref(),
+                ))
+            } else {
+                None
+            };
+            if render {
+                let rendered = nearest_map.render_text(&coordinates, safe_region.as_deref());
+                write!(io::stdout(), "{}", rendered)?;
+            }
+            if let Some(path) = image_path {
+                let image = nearest_map.render_ppm(&coordinates, safe_region.as_deref());
+                fs::write(&path, image).context("failed to write image")?;
+            }
+        }
+    }
+
+    let areas = if metric_name == "manhattan" {
+        calculate_safe_region_areas(&coordinates, &[threshold])[0]
+    } else {
+        calculate_largest_areas_nearest_to_all_coordinates(
+            &coordinates,
+            &center,
+            threshold,
+            metric.as_ref(),
+        ) as u64
+    };
     writeln!(io::stdout(), "areas within acceptable ranges: {}", areas)?;
 
+    if let Some(thresholds) = env::args().skip_while(|arg| arg != "--thresholds").nth(1) {
+        let thresholds = thresholds
+            .split(',')
+            .map(|threshold| threshold.trim().parse().context("invalid threshold"))
+            .collect::<Result<Vec<u64>>>()?;
+        let areas = calculate_safe_region_areas(&coordinates, &thresholds);
+        for (threshold, areas) in thresholds.iter().zip(areas) {
+            writeln!(
+                io::stdout(),
+                "areas within total distance {}: {}",
+                threshold,
+                areas
+            )?;
+        }
+    }
+
     Ok(())
 }
@@ -4,92 +4,188 @@ extern crate lazy_static;
 use anyhow::{anyhow, Context, Error, Result};
 use regex::Regex;
 use std::{
-    collections::HashMap,
-    fs::File,
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    env,
+    fmt::Display,
+    fs::{self, File},
+    hash::Hash,
     io::{self, BufRead, BufReader, Write},
     str::FromStr,
 };
 
-type Step = char;
+type Step = String;
 
-struct Dependency {
-    predecessor: Step,
-    successor: Step,
+struct Dependency<S = Step> {
+    predecessor: S,
+    successor: S,
 }
 
-struct DAG {
-    ready_to_run: Vec<Step>,
-    prerequisites: HashMap<Step, Vec<Step>>,
-    next_steps: HashMap<Step, Vec<Step>>,
+#[allow(clippy::upper_case_acronyms)]
+struct DAG<S = Step> {
+    steps: Vec<S>,
+    ready_to_run: Vec<S>,
+    prerequisites: HashMap<S, Vec<S>>,
+    next_steps: HashMap<S, Vec<S>>,
 }
 
-impl DAG {
-    fn new(dependencies: &[Dependency]) -> Self {
-        let mut prerequisites = HashMap::with_capacity(25);
-        let mut next_steps = HashMap::with_capacity(25);
-        dependencies.iter().for_each(|dep| {
+impl<S: Clone + Eq + Hash + Display> DAG<S> {
+    fn new(steps: impl IntoIterator<Item = S>, dependencies: &[Dependency<S>]) -> Result<Self> {
+        let mut prerequisites: HashMap<S, Vec<S>> = HashMap::with_capacity(25);
+        let mut next_steps: HashMap<S, Vec<S>> = HashMap::with_capacity(25);
+        let mut known_steps = HashSet::new();
+        let mut all_steps = Vec::new();
+        let mut add_step = |step: &S| {
+            if known_steps.insert(step.clone()) {
+                all_steps.push(step.clone());
+            }
+        };
+        for step in steps {
+            add_step(&step);
+        }
+        for dep in dependencies {
+            add_step(&dep.predecessor);
+            add_step(&dep.successor);
+            let successors = next_steps.entry(dep.predecessor.clone()).or_default();
+            if successors.contains(&dep.successor) {
+                return Err(anyhow!(
+                    "duplicate dependency: {} -> {}",
+                    dep.predecessor,
+                    dep.successor
+                ));
+            }
+            successors.push(dep.successor.clone());
             prerequisites
-                .entry(dep.successor)
-                .or_insert_with(Vec::new)
-                .push(dep.predecessor);
-            next_steps
-                .entry(dep.predecessor)
-                .or_insert_with(Vec::new)
-                .push(dep.successor);
-        });
+                .entry(dep.successor.clone())
+                .or_default()
+                .push(dep.predecessor.clone());
+        }
 
-        let ready_to_run = next_steps
-            .keys()
+        let ready_to_run = all_steps
+            .iter()
             .filter(|step| !prerequisites.contains_key(step))
             .cloned()
             .collect::<Vec<_>>();
 
-        DAG {
+        let dag = DAG {
+            steps: all_steps,
             prerequisites,
             next_steps,
             ready_to_run,
+        };
+        if let Some(cycle) = dag.find_cycle() {
+            let cycle = cycle
+                .iter()
+                .map(|step| step.to_string())
+                .collect::<Vec<_>>();
+            return Err(anyhow!("cycle detected: {}", cycle.join(" -> ")));
+        }
+
+        Ok(dag)
+    }
+
+    fn isolated_steps(&self) -> Vec<&S> {
+        self.steps
+            .iter()
+            .filter(|step| {
+                !self.prerequisites.contains_key(step) && !self.next_steps.contains_key(step)
+            })
+            .collect()
+    }
+
+    fn topological_order(&self) -> (Vec<&S>, HashMap<&S, usize>) {
+        let mut pending = self
+            .prerequisites
+            .iter()
+            .map(|(step, prerequisite)| (step, prerequisite.len()))
+            .collect::<HashMap<_, _>>();
+        let mut ready = self.ready_to_run.iter().collect::<Vec<_>>();
+        let mut order = Vec::with_capacity(self.steps.len());
+        while let Some(step) = ready.pop() {
+            order.push(step);
+            for next_step in self.next_steps.get(step).into_iter().flatten() {
+                if let Some(count) = pending.get_mut(next_step) {
+                    *count -= 1;
+                    if *count == 0 {
+                        pending.remove(next_step);
+                        ready.push(next_step);
+                    }
+                }
+            }
         }
+
+        (order, pending)
     }
 
-    fn run_sequences(&self, worker: usize) -> (String, u64) {
-        let mut sequences = String::with_capacity(25);
+    fn find_cycle(&self) -> Option<Vec<S>> {
+        let (_, pending) = self.topological_order();
+        let mut step = self.steps.iter().find(|step| pending.contains_key(step))?;
+        let mut path = vec![step];
+        loop {
+            step = self.prerequisites[step]
+                .iter()
+                .find(|prerequisite| pending.contains_key(prerequisite))?;
+            if let Some(start) = path.iter().position(|visited| *visited == step) {
+                let mut cycle = path[start..]
+                    .iter()
+                    .rev()
+                    .cloned()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                cycle.push(cycle[0].clone());
+                return Some(cycle);
+            }
+            path.push(step);
+        }
+    }
+
+    fn run_sequences<D, P>(&self, worker: usize, duration: D, priority: P) -> Schedule<S>
+    where
+        D: Fn(&S) -> u64,
+        P: Fn(&S, &S) -> Ordering,
+    {
+        assert!(worker > 0, "at least one worker is required");
+        let mut sequences = Vec::with_capacity(25);
         let mut timer = 0u64;
         let mut ready_to_run = self.ready_to_run.clone();
         let next_steps = &self.next_steps;
         let mut prerequisites = self.prerequisites.clone();
-        let mut available_workers = (0..worker).collect::<Vec<_>>();
+        let mut available_workers = (0..worker).rev().collect::<Vec<_>>();
         let mut occupied_workers = HashMap::new();
+        let mut assignments = Vec::with_capacity(25);
 
         loop {
-            ready_to_run.sort_by(|t1, t2| t2.cmp(t1));
+            ready_to_run.sort_by(|t1, t2| priority(t2, t1));
 
             while !ready_to_run.is_empty() && !available_workers.is_empty() {
                 let ready_step = ready_to_run.pop().unwrap();
-                let completion_time = 60 + (ready_step as u8 - b'A' + 1) as u64;
+                let completion_time = duration(&ready_step);
                 let worker = available_workers.pop().unwrap();
 
+                assignments.push(Assignment {
+                    worker,
+                    step: ready_step.clone(),
+                    start: timer,
+                    end: timer + completion_time,
+                });
                 occupied_workers.insert(worker, (completion_time, ready_step));
             }
 
-            if let Some(wait_time) = occupied_workers
-                .values()
-                .filter(|(t, _)| *t > 0)
-                .map(|(t, _)| *t)
-                .min()
-            {
+            if let Some(wait_time) = occupied_workers.values().map(|(t, _)| *t).min() {
                 let mut finished_steps = vec![];
                 occupied_workers.iter_mut().for_each(|(w, (t, s))| {
                     if *t == wait_time {
                         available_workers.push(*w);
-                        finished_steps.push(*s);
+                        finished_steps.push(s.clone());
                     } else {
                         *t -= wait_time;
                     }
                 });
                 for worker in &available_workers {
-                    occupied_workers.remove(&worker);
+                    occupied_workers.remove(worker);
                 }
-                finished_steps.sort();
+                available_workers.sort_by(|w1, w2| w2.cmp(w1));
+                finished_steps.sort_by(|t1, t2| priority(t1, t2));
                 for finish_step in finished_steps {
                     if let Some(steps) = next_steps.get(&finish_step) {
                         steps.iter().for_each(|next_step| {
@@ -108,7 +204,7 @@ impl DAG {
                                     false
                                 };
                             if complete {
-                                ready_to_run.push(*next_step);
+                                ready_to_run.push(next_step.clone());
                             }
                         });
                     }
@@ -122,7 +218,335 @@ impl DAG {
             }
         }
 
-        (sequences, timer)
+        Schedule {
+            sequences,
+            total_time: timer,
+            workers: worker,
+            assignments,
+        }
+    }
+}
+
+impl<S: Clone + Eq + Hash + Display> DAG<S> {
+    fn analyze_critical_path<D, P>(&self, duration: D, priority: P) -> CriticalPath<S>
+    where
+        D: Fn(&S) -> u64,
+        P: Fn(&S, &S) -> Ordering,
+    {
+        let (order, _) = self.topological_order();
+        let mut earliest_start: HashMap<&S, u64> = HashMap::with_capacity(order.len());
+        for step in &order {
+            let start = self
+                .prerequisites
+                .get(*step)
+                .into_iter()
+                .flatten()
+                .map(|prerequisite| earliest_start[prerequisite] + duration(prerequisite))
+                .max()
+                .unwrap_or(0);
+            earliest_start.insert(step, start);
+        }
+        let makespan = order
+            .iter()
+            .map(|step| earliest_start[*step] + duration(step))
+            .max()
+            .unwrap_or(0);
+
+        let mut latest_start: HashMap<&S, u64> = HashMap::with_capacity(order.len());
+        for step in order.iter().rev() {
+            let finish = self
+                .next_steps
+                .get(*step)
+                .into_iter()
+                .flatten()
+                .map(|next_step| latest_start[next_step])
+                .min()
+                .unwrap_or(makespan);
+            latest_start.insert(step, finish - duration(step));
+        }
+
+        let mut timings = order
+            .iter()
+            .map(|step| StepTiming {
+                step: (*step).clone(),
+                earliest_start: earliest_start[*step],
+                latest_start: latest_start[*step],
+                slack: latest_start[*step] - earliest_start[*step],
+            })
+            .collect::<Vec<_>>();
+        timings.sort_by(|t1, t2| {
+            t1.earliest_start
+                .cmp(&t2.earliest_start)
+                .then_with(|| priority(&t1.step, &t2.step))
+        });
+
+        let is_critical = |step: &S| earliest_start[step] == latest_start[step];
+        let mut path = Vec::new();
+        let mut candidates = timings
+            .iter()
+            .filter(|timing| timing.slack == 0 && timing.earliest_start == 0)
+            .map(|timing| &timing.step)
+            .collect::<Vec<_>>();
+        while let Some(step) = candidates.first() {
+            path.push((*step).clone());
+            let finish = earliest_start[*step] + duration(step);
+            let mut next_candidates = self
+                .next_steps
+                .get(*step)
+                .into_iter()
+                .flatten()
+                .filter(|next_step| is_critical(next_step) && earliest_start[next_step] == finish)
+                .collect::<Vec<_>>();
+            next_candidates.sort_by(|t1, t2| priority(t1, t2));
+            candidates = next_candidates;
+        }
+
+        let min_workers = (1..=self.steps.len().max(1))
+            .find(|&workers| {
+                self.run_sequences(workers, &duration, &priority).total_time == makespan
+            })
+            .unwrap_or_else(|| self.steps.len().max(1));
+
+        CriticalPath {
+            makespan,
+            path,
+            timings,
+            min_workers,
+        }
+    }
+}
+
+impl<S: Clone + Eq + Hash + Display> DAG<S> {
+    fn node_label<D>(
+        &self,
+        step: &S,
+        duration: &D,
+        schedule: Option<&Schedule<S>>,
+        separator: &str,
+    ) -> String
+    where
+        D: Fn(&S) -> u64,
+    {
+        let mut label = format!("{}{}{}t", step, separator, duration(step));
+        if let Some(schedule) = schedule {
+            if let Some(order) = schedule.sequences.iter().position(|s| s == step) {
+                label.push_str(&format!("{}order {}", separator, order + 1));
+            }
+            if let Some(assignment) = schedule.assignments.iter().find(|a| a.step == *step) {
+                label.push_str(&format!(
+                    "{}worker {} [{}-{}]",
+                    separator,
+                    assignment.worker + 1,
+                    assignment.start,
+                    assignment.end
+                ));
+            }
+        }
+        label
+    }
+
+    fn to_dot<D>(&self, duration: D, schedule: Option<&Schedule<S>>) -> String
+    where
+        D: Fn(&S) -> u64,
+    {
+        let escape = |text: String| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph steps {\n    rankdir=LR;\n    node [shape=box];\n");
+        for step in &self.steps {
+            dot.push_str(&format!(
+                "    \"{}\" [label=\"{}\"];\n",
+                escape(step.to_string()),
+                escape(self.node_label(step, &duration, schedule, "\n")).replace('\n', "\\n")
+            ));
+        }
+        for step in &self.steps {
+            for next_step in self.next_steps.get(step).into_iter().flatten() {
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\";\n",
+                    escape(step.to_string()),
+                    escape(next_step.to_string())
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn to_mermaid<D>(&self, duration: D, schedule: Option<&Schedule<S>>) -> String
+    where
+        D: Fn(&S) -> u64,
+    {
+        let ids = self
+            .steps
+            .iter()
+            .enumerate()
+            .map(|(idx, step)| (step, format!("n{}", idx)))
+            .collect::<HashMap<_, _>>();
+        let mut mermaid = String::from("flowchart LR\n");
+        for step in &self.steps {
+            mermaid.push_str(&format!(
+                "    {}[\"{}\"]\n",
+                ids[step],
+                self.node_label(step, &duration, schedule, "<br/>")
+                    .replace('"', "#quot;")
+            ));
+        }
+        for step in &self.steps {
+            for next_step in self.next_steps.get(step).into_iter().flatten() {
+                mermaid.push_str(&format!("    {} --> {}\n", ids[step], ids[next_step]));
+            }
+        }
+        mermaid
+    }
+}
+
+struct StepTiming<S> {
+    step: S,
+    earliest_start: u64,
+    latest_start: u64,
+    slack: u64,
+}
+
+struct CriticalPath<S> {
+    makespan: u64,
+    path: Vec<S>,
+    timings: Vec<StepTiming<S>>,
+    min_workers: usize,
+}
+
+struct Assignment<S> {
+    worker: usize,
+    step: S,
+    start: u64,
+    end: u64,
+}
+
+struct Schedule<S> {
+    sequences: Vec<S>,
+    total_time: u64,
+    workers: usize,
+    assignments: Vec<Assignment<S>>,
+}
+
+impl<S: Display> Schedule<S> {
+    fn render_table(&self) -> String {
+        let width = self
+            .assignments
+            .iter()
+            .map(|assignment| assignment.step.to_string().len())
+            .max()
+            .unwrap_or(1)
+            .max(9);
+        let mut table = format!("{:<8}", "Second");
+        for worker in 1..=self.workers {
+            table.push_str(&format!(
+                " {:^width$}",
+                format!("Worker {}", worker),
+                width = width
+            ));
+        }
+        table.push_str(" Done\n");
+
+        let mut finished = self.assignments.iter().collect::<Vec<_>>();
+        finished.sort_by_key(|assignment| assignment.end);
+        for second in 0..self.total_time {
+            table.push_str(&format!("{:>6}  ", second));
+            for worker in 0..self.workers {
+                let running = self.assignments.iter().find(|assignment| {
+                    assignment.worker == worker
+                        && assignment.start <= second
+                        && second < assignment.end
+                });
+                let cell = running.map_or_else(|| ".".to_string(), |a| a.step.to_string());
+                table.push_str(&format!(" {:^width$}", cell, width = width));
+            }
+            table.push(' ');
+            finished
+                .iter()
+                .take_while(|assignment| assignment.end <= second)
+                .for_each(|assignment| table.push_str(&assignment.step.to_string()));
+            table.push('\n');
+        }
+        table
+    }
+
+    fn render_gantt(&self, columns: u64) -> String {
+        let scale = self.total_time.div_ceil(columns).max(1);
+        let label_width = self
+            .assignments
+            .iter()
+            .map(|assignment| assignment.step.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let width = self.total_time.div_ceil(scale) as usize;
+        let mut gantt = String::new();
+        for assignment in &self.assignments {
+            let start = ((assignment.start / scale) as usize).min(width);
+            let end = if assignment.end > assignment.start {
+                (assignment.end.div_ceil(scale) as usize).max(start + 1)
+            } else {
+                start
+            }
+            .min(width);
+            gantt.push_str(&format!(
+                "{:<label_width$} w{} |{}{}{}| {}-{}\n",
+                assignment.step.to_string(),
+                assignment.worker + 1,
+                " ".repeat(start),
+                "#".repeat(end - start),
+                " ".repeat(width - end),
+                assignment.start,
+                assignment.end,
+                label_width = label_width
+            ));
+        }
+        gantt
+    }
+
+    fn render_svg(&self) -> String {
+        let scale = (1200.0 / self.total_time.max(1) as f64).min(20.0);
+        let lane = 30;
+        let width = (self.total_time as f64 * scale).ceil() as usize + 80;
+        let height = lane * self.workers + 20;
+        let escape = |text: String| {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        };
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+            width, height
+        );
+        for worker in 0..self.workers {
+            svg.push_str(&format!(
+                "  <text x=\"4\" y=\"{}\">worker {}</text>\n",
+                worker * lane + 20,
+                worker + 1
+            ));
+        }
+        for assignment in &self.assignments {
+            let x = 70.0 + assignment.start as f64 * scale;
+            let w = (assignment.end - assignment.start) as f64 * scale;
+            let y = assignment.worker * lane + 5;
+            svg.push_str(&format!(
+                "  <rect x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"#8ab\" stroke=\"#345\"><title>{} {}-{}</title></rect>\n",
+                x,
+                y,
+                w,
+                lane - 10,
+                escape(assignment.step.to_string()),
+                assignment.start,
+                assignment.end
+            ));
+            svg.push_str(&format!(
+                "  <text x=\"{:.1}\" y=\"{}\">{}</text>\n",
+                x + 2.0,
+                y + 15,
+                escape(assignment.step.to_string())
+            ));
+        }
+        svg.push_str("</svg>\n");
+        svg
     }
 }
 
@@ -132,7 +556,7 @@ impl FromStr for Dependency {
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         lazy_static! {
             static ref RE: Regex = Regex::new(
-                r"Step (?P<predecessor>[A-Z]) must be finished before step (?P<successor>[A-Z]) can begin."
+                r"Step (?P<predecessor>\S+) must be finished before step (?P<successor>\S+) can begin."
             )
             .unwrap();
         }
@@ -148,6 +572,31 @@ impl FromStr for Dependency {
     }
 }
 
+fn alphabet_duration(base: u64) -> impl Fn(&Step) -> u64 {
+    move |step| match step.as_bytes() {
+        [letter @ b'A'..=b'Z'] => base + (letter - b'A' + 1) as u64,
+        _ => base,
+    }
+}
+
+type Priority = Box<dyn Fn(&Step, &Step) -> Ordering>;
+
+fn priority_by_name(policy: &str) -> Result<Priority> {
+    match policy {
+        "alphabetical" => Ok(Box::new(|t1: &Step, t2: &Step| t1.cmp(t2))),
+        "reverse" => Ok(Box::new(|t1: &Step, t2: &Step| t2.cmp(t1))),
+        _ => Err(anyhow!("unknown priority policy: {}", policy)),
+    }
+}
+
+fn format_sequences(sequences: &[Step]) -> String {
+    if sequences.iter().all(|step| step.chars().count() == 1) {
+        sequences.concat()
+    } else {
+        sequences.join(",")
+    }
+}
+
 fn main() -> Result<()> {
     let file = File::open("2018/day-07/input/input.txt").context("failed to read input file")?;
     let reader = BufReader::new(file);
@@ -156,23 +605,104 @@ fn main() -> Result<()> {
         .lines()
         .filter_map(|line| line.ok().and_then(|s| s.parse::<Dependency>().ok()))
         .collect::<Vec<_>>();
-    let dag = DAG::new(&dependencies);
+    let steps = env::args()
+        .skip_while(|arg| arg != "--steps")
+        .nth(1)
+        .map(|steps| steps.split(',').map(|step| step.to_string()).collect())
+        .unwrap_or_else(Vec::new);
+    let dag = DAG::new(steps, &dependencies)?;
+    let isolated_steps = dag.isolated_steps();
+    if !isolated_steps.is_empty() {
+        writeln!(
+            io::stdout(),
+            "steps without dependencies: {}",
+            isolated_steps
+                .iter()
+                .map(|step| step.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        )?;
+    }
+
+    let base_duration = match env::args()
+        .skip_while(|arg| arg != "--base-duration")
+        .nth(1)
+    {
+        Some(base) => base.parse().context("invalid base duration")?,
+        None => 60,
+    };
+    let workers = match env::args().skip_while(|arg| arg != "--workers").nth(1) {
+        Some(workers) => workers.parse().context("invalid worker count")?,
+        None => 5,
+    };
+    if workers == 0 {
+        return Err(anyhow!("worker count must be at least 1"));
+    }
+    let priority = priority_by_name(
+        &env::args()
+            .skip_while(|arg| arg != "--priority")
+            .nth(1)
+            .unwrap_or_else(|| "alphabetical".to_string()),
+    )?;
 
-    let run_sequences = dag.run_sequences(1);
+    let schedule = dag.run_sequences(1, alphabet_duration(base_duration), &priority);
     writeln!(
         io::stdout(),
         "sequences of task with 1 worker: {}, takes {} time unit",
-        run_sequences.0,
-        run_sequences.1
+        format_sequences(&schedule.sequences),
+        schedule.total_time
     )?;
 
-    let run_sequences_5worker = dag.run_sequences(5);
+    let schedule = dag.run_sequences(workers, alphabet_duration(base_duration), &priority);
     writeln!(
         io::stdout(),
-        "sequences of task with 5 worker: {}, takes {} time unit",
-        run_sequences_5worker.0,
-        run_sequences_5worker.1
+        "sequences of task with {} worker: {}, takes {} time unit",
+        workers,
+        format_sequences(&schedule.sequences),
+        schedule.total_time
     )?;
+    if env::args().any(|arg| arg == "--critical-path") {
+        let critical_path = dag.analyze_critical_path(alphabet_duration(base_duration), &priority);
+        writeln!(
+            io::stdout(),
+            "minimum makespan {} time unit, reached with {} worker(s), critical path: {}",
+            critical_path.makespan,
+            critical_path.min_workers,
+            critical_path.path.join(" -> ")
+        )?;
+        for timing in &critical_path.timings {
+            writeln!(
+                io::stdout(),
+                "{}: earliest start {}, latest start {}, slack {}",
+                timing.step,
+                timing.earliest_start,
+                timing.latest_start,
+                timing.slack
+            )?;
+        }
+    }
+    let annotation = if env::args().any(|arg| arg == "--annotate") {
+        Some(&schedule)
+    } else {
+        None
+    };
+    if let Some(path) = env::args().skip_while(|arg| arg != "--dot").nth(1) {
+        let dot = dag.to_dot(alphabet_duration(base_duration), annotation);
+        fs::write(&path, dot).context("failed to write dot graph")?;
+    }
+    if let Some(path) = env::args().skip_while(|arg| arg != "--mermaid").nth(1) {
+        let mermaid = dag.to_mermaid(alphabet_duration(base_duration), annotation);
+        fs::write(&path, mermaid).context("failed to write mermaid graph")?;
+    }
+    if env::args().any(|arg| arg == "--table") {
+        write!(io::stdout(), "{}", schedule.render_table())?;
+    }
+    if env::args().any(|arg| arg == "--gantt") {
+        write!(io::stdout(), "{}", schedule.render_gantt(100))?;
+    }
+    if let Some(path) = env::args().skip_while(|arg| arg != "--svg").nth(1) {
+        fs::write(&path, schedule.render_svg()).context("failed to write svg")?;
+    }
 
     Ok(())
 }
@@ -1,7 +1,9 @@
-use anyhow::{anyhow, Context, Error, Result};
+use anyhow::{anyhow, Context, Result};
 use std::{
+    env, fmt,
     fs::File,
     io::{self, BufRead, BufReader, Write},
+    slice,
 };
 
 struct Node {
@@ -9,121 +11,389 @@ struct Node {
     metadatas: Vec<u32>,
 }
 
-fn construct_nodes(s: String) -> Result<Vec<Node>, Error> {
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut children = std::mem::take(&mut self.children);
+        while let Some(mut child) = children.pop() {
+            children.append(&mut child.children);
+        }
+    }
+}
+
+enum ParseErrorKind {
+    InvalidToken(String),
+    UnexpectedEnd,
+    TrailingData(String),
+    MultipleRoots,
+}
+
+struct ParseError {
+    offset: usize,
+    path: Vec<usize>,
+    expected: String,
+    kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "token {}", self.offset)?;
+        if !self.path.is_empty() {
+            let path = self
+                .path
+                .iter()
+                .map(|idx| idx.to_string())
+                .collect::<Vec<_>>();
+            write!(f, " in node {}", path.join("."))?;
+        }
+        write!(f, ": expected {}, found ", self.expected)?;
+        match &self.kind {
+            ParseErrorKind::InvalidToken(token) => write!(f, "invalid token {:?}", token),
+            ParseErrorKind::UnexpectedEnd => write!(f, "end of data"),
+            ParseErrorKind::TrailingData(token) => write!(f, "trailing data {:?}", token),
+            ParseErrorKind::MultipleRoots => write!(f, "another root node"),
+        }
+    }
+}
+
+impl fmt::Debug for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Tokens<'s> {
+    tokens: std::iter::Peekable<std::str::SplitWhitespace<'s>>,
+    offset: usize,
+}
+
+impl<'s> Tokens<'s> {
+    fn next_number(&mut self, path: &[usize], expected: String) -> Result<u32, ParseError> {
+        let offset = self.offset;
+        let error = |kind| ParseError {
+            offset,
+            path: path.to_vec(),
+            expected: expected.clone(),
+            kind,
+        };
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| error(ParseErrorKind::UnexpectedEnd))?;
+        self.offset += 1;
+        token
+            .parse()
+            .map_err(|_| error(ParseErrorKind::InvalidToken(token.to_string())))
+    }
+}
+
+fn construct_node(tokens: &mut Tokens, root_idx: usize) -> Result<Node, ParseError> {
     let mut stack_nodes: Vec<(u32, u32, Node)> = Vec::new();
-    let mut nodes = Vec::new();
-    let mut tokens = s.split(' ').filter_map(|token| token.parse::<u32>().ok());
+    let mut path = vec![root_idx];
 
     loop {
-        match (tokens.next(), tokens.next()) {
-            (Some(0), Some(metadata_qty)) => {
-                let mut metadatas = vec![];
-                for _ in 0..metadata_qty {
-                    if let Some(metadata) = tokens.next() {
-                        metadatas.push(metadata)
-                    } else {
-                        return Err(anyhow!("invalid end of data"));
-                    }
-                }
-                let mut node = Node {
-                    children: vec![],
-                    metadatas,
-                };
+        let child_qty = tokens.next_number(&path, "child count".to_string())?;
+        let metadata_qty = tokens.next_number(&path, "metadata count".to_string())?;
+        let node = Node {
+            children: Vec::new(),
+            metadatas: Vec::new(),
+        };
+        stack_nodes.push((child_qty, metadata_qty, node));
 
-                loop {
-                    match stack_nodes.pop() {
-                        None => {
-                            nodes.push(node);
-                            break;
-                        }
-                        Some((1, metadata_qty, mut unfinished_node)) => {
-                            for _ in 0..metadata_qty {
-                                if let Some(metadata) = tokens.next() {
-                                    unfinished_node.metadatas.push(metadata)
-                                } else {
-                                    return Err(anyhow!("invalid end of data"));
-                                }
-                            }
-                            unfinished_node.children.push(node);
-                            node = unfinished_node;
-                        }
-                        Some((unprocessed_child, metadata_qty, mut unfinished_node)) => {
-                            unfinished_node.children.push(node);
-                            stack_nodes.push((
-                                unprocessed_child - 1,
-                                metadata_qty,
-                                unfinished_node,
-                            ));
-                            break;
-                        }
-                    }
-                }
+        loop {
+            let (unprocessed_child, metadata_qty, unfinished_node) =
+                stack_nodes.last_mut().unwrap();
+            if *unprocessed_child > 0 {
+                *unprocessed_child -= 1;
+                path.push(unfinished_node.children.len());
+                break;
             }
-            (Some(child_qty), Some(metadata_qty)) => {
-                let node = Node {
-                    children: Vec::with_capacity(child_qty as usize),
-                    metadatas: Vec::with_capacity(metadata_qty as usize),
-                };
-                stack_nodes.push((child_qty, metadata_qty, node));
+            for idx in 0..*metadata_qty {
+                let expected = format!("metadata entry {} of {}", idx + 1, metadata_qty);
+                let metadata = tokens.next_number(&path, expected)?;
+                unfinished_node.metadatas.push(metadata);
+            }
+
+            let (_, _, node) = stack_nodes.pop().unwrap();
+            path.pop();
+            match stack_nodes.last_mut() {
+                Some((_, _, parent)) => parent.children.push(node),
+                None => return Ok(node),
             }
-            (None, None) => break,
-            _ => return Err(anyhow!("invalid end of data")),
         }
     }
-    Ok(nodes)
 }
 
-fn traverse_calculate_metadatas(nodes: &[Node]) -> u32 {
-    let mut sum_metadata = 0;
-    let mut nodes_stack = Vec::new();
-    nodes_stack.push(nodes.iter());
-    while let Some(node_iter) = nodes_stack.last_mut() {
+fn construct_nodes(s: &str) -> Result<Vec<Node>, ParseError> {
+    let mut tokens = Tokens {
+        tokens: s.split_whitespace().peekable(),
+        offset: 0,
+    };
+    let root = construct_node(&mut tokens, 0)?;
+
+    if let Some(token) = tokens.tokens.peek() {
+        let offset = tokens.offset;
+        let token = token.to_string();
+        let kind = match construct_node(&mut tokens, 1) {
+            Ok(_) => ParseErrorKind::MultipleRoots,
+            Err(_) => ParseErrorKind::TrailingData(token),
+        };
+        return Err(ParseError {
+            offset,
+            path: vec![],
+            expected: "end of data".to_string(),
+            kind,
+        });
+    }
+
+    Ok(vec![root])
+}
+
+enum Visit<'node> {
+    Pre { node: &'node Node, depth: usize },
+    Post { node: &'node Node, depth: usize },
+}
+
+struct Traversal<'node> {
+    nodes_stack: Vec<(Option<&'node Node>, slice::Iter<'node, Node>)>,
+}
+
+impl<'node> Traversal<'node> {
+    fn new(nodes: &'node [Node]) -> Self {
+        Traversal {
+            nodes_stack: vec![(None, nodes.iter())],
+        }
+    }
+}
+
+impl<'node> Iterator for Traversal<'node> {
+    type Item = Visit<'node>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (parent, node_iter) = self.nodes_stack.last_mut()?;
         if let Some(node) = node_iter.next() {
-            sum_metadata += node.metadatas.iter().sum::<u32>();
-            if !node.children.is_empty() {
-                nodes_stack.push(node.children.iter());
-            }
+            let depth = self.nodes_stack.len() - 1;
+            self.nodes_stack.push((Some(node), node.children.iter()));
+            Some(Visit::Pre { node, depth })
         } else {
-            nodes_stack.pop();
+            let parent = *parent;
+            self.nodes_stack.pop();
+            match parent {
+                Some(node) => Some(Visit::Post {
+                    node,
+                    depth: self.nodes_stack.len() - 1,
+                }),
+                None => None,
+            }
         }
     }
-    sum_metadata
 }
 
-fn calculate_value_node(node: &Node) -> u32 {
-    if node.children.is_empty() {
-        node.metadatas.iter().sum::<u32>()
-    } else {
-        let mut children_values = vec![None; node.children.len()];
-        let mut value = 0u32;
-        for idx in &node.metadatas {
-            let idx = *idx as usize;
-            if idx <= node.children.len() && idx != 0 {
-                if let Some(child_value) = children_values[idx - 1] {
-                    value += child_value;
+fn traverse_calculate_metadatas(nodes: &[Node]) -> u32 {
+    Traversal::new(nodes)
+        .map(|visit| match visit {
+            Visit::Pre { node, .. } => node.metadatas.iter().sum::<u32>(),
+            Visit::Post { .. } => 0,
+        })
+        .sum()
+}
+
+fn calculate_values(nodes: &[Node]) -> Vec<u32> {
+    let mut values = Vec::new();
+    let mut pending = Vec::new();
+    let mut children_values = Vec::new();
+    for visit in Traversal::new(nodes) {
+        match visit {
+            Visit::Pre { .. } => {
+                pending.push(values.len());
+                values.push(0);
+            }
+            Visit::Post { node, .. } => {
+                let children_start = children_values.len() - node.children.len();
+                let value = if node.children.is_empty() {
+                    node.metadatas.iter().sum::<u32>()
                 } else {
-                    let child_value = calculate_value_node(&node.children[idx - 1]);
-                    children_values[idx - 1] = Some(child_value);
-                    value += child_value;
+                    node.metadatas
+                        .iter()
+                        .filter_map(|idx| {
+                            let idx = *idx as usize;
+                            if idx <= node.children.len() && idx != 0 {
+                                Some(children_values[children_start + idx - 1])
+                            } else {
+                                None
+                            }
+                        })
+                        .sum()
+                };
+                children_values.truncate(children_start);
+                children_values.push(value);
+                values[pending.pop().unwrap()] = value;
+            }
+        }
+    }
+    values
+}
+
+fn calculate_value_node(node: &Node) -> u32 {
+    calculate_values(slice::from_ref(node))[0]
+}
+
+fn serialize_nodes(nodes: &[Node]) -> String {
+    let mut tokens = Vec::new();
+    for visit in Traversal::new(nodes) {
+        match visit {
+            Visit::Pre { node, .. } => {
+                tokens.push(node.children.len().to_string());
+                tokens.push(node.metadatas.len().to_string());
+            }
+            Visit::Post { node, .. } => {
+                tokens.extend(node.metadatas.iter().map(|metadata| metadata.to_string()))
+            }
+        }
+    }
+    tokens.join(" ")
+}
+
+fn pretty_print_nodes(nodes: &[Node]) -> String {
+    let mut printed = String::new();
+    let mut values = calculate_values(nodes).into_iter();
+    for visit in Traversal::new(nodes) {
+        if let Visit::Pre { node, depth } = visit {
+            printed.push_str(&format!(
+                "{}- {} children, metadata {:?}, value {}\n",
+                "  ".repeat(depth),
+                node.children.len(),
+                node.metadatas,
+                values.next().unwrap_or(0)
+            ));
+        }
+    }
+    printed
+}
+
+fn same_trees(nodes: &[Node], other_nodes: &[Node]) -> bool {
+    let mut traversal = Traversal::new(nodes);
+    let mut other_traversal = Traversal::new(other_nodes);
+    loop {
+        match (traversal.next(), other_traversal.next()) {
+            (None, None) => return true,
+            (Some(Visit::Pre { node, .. }), Some(Visit::Pre { node: other, .. })) => {
+                if node.children.len() != other.children.len() || node.metadatas != other.metadatas
+                {
+                    return false;
                 }
             }
+            (Some(Visit::Post { .. }), Some(Visit::Post { .. })) => {}
+            _ => return false,
         }
-        value
     }
 }
 
+fn check_round_trip(nodes: &[Node]) -> Result<()> {
+    let serialized = serialize_nodes(nodes);
+    let reparsed = construct_nodes(&serialized)?;
+    if !same_trees(&reparsed, nodes) {
+        return Err(anyhow!("round trip changed the tree"));
+    }
+    if serialize_nodes(&reparsed) != serialized {
+        return Err(anyhow!("round trip changed the serialization"));
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let file = File::open("2018/day-08/input/input.txt").context("failed to read input file")?;
     let reader = BufReader::new(file);
 
-    if let Some(s) = reader.lines().filter_map(|line| line.ok()).next() {
-        if let Ok(nodes) = construct_nodes(s) {
-            let checksum_metadata = traverse_calculate_metadatas(&nodes);
-            writeln!(io::stdout(), "checksum metadata: {}", checksum_metadata)?;
+    if let Some(s) = reader.lines().map_while(Result::ok).next() {
+        let nodes = construct_nodes(&s).context("malformed license data")?;
+        let checksum_metadata = traverse_calculate_metadatas(&nodes);
+        writeln!(io::stdout(), "checksum metadata: {}", checksum_metadata)?;
+
+        let value_node = calculate_value_node(&nodes[0]);
+        writeln!(io::stdout(), "root node value: {}", value_node)?;
 
-            let value_node = calculate_value_node(&nodes[0]);
-            writeln!(io::stdout(), "root node value: {}", value_node)?;
+        if env::args().any(|arg| arg == "--pretty") {
+            write!(io::stdout(), "{}", pretty_print_nodes(&nodes))?;
         }
+        if env::args().any(|arg| arg == "--round-trip") {
+            check_round_trip(&nodes).context("round trip failed for input")?;
+            writeln!(io::stdout(), "round trip passed for input")?;
+        }
+    }
+    if let Some(depth) = env::args().skip_while(|arg| arg != "--deep").nth(1) {
+        let depth = depth.parse::<usize>().context("invalid depth")?;
+        let mut s = "1 1 ".repeat(depth);
+        s.push_str("0 1 5");
+        s.push_str(&" 1".repeat(depth));
+        let nodes = construct_nodes(&s)?;
+        let deepest = Traversal::new(&nodes)
+            .filter_map(|visit| match visit {
+                Visit::Post { depth, .. } => Some(depth),
+                Visit::Pre { .. } => None,
+            })
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            io::stdout(),
+            "tree of depth {}: checksum metadata {}, root node value {}",
+            deepest,
+            traverse_calculate_metadatas(&nodes),
+            calculate_value_node(&nodes[0])
+        )?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_node(state: &mut u64, depth: u32) -> Node {
+        let mut next = |bound: u64| {
+            *state ^= *state << 13;
+            *state ^= *state >> 7;
+            *state ^= *state << 17;
+            (*state % bound) as u32
+        };
+        let child_qty = if depth == 0 { 0 } else { next(4) };
+        let metadata_qty = next(4) + 1;
+        let metadatas = (0..metadata_qty).map(|_| next(5)).collect::<Vec<_>>();
+        let children = (0..child_qty)
+            .map(|_| generate_node(state, depth - 1))
+            .collect();
+        Node {
+            children,
+            metadatas,
+        }
+    }
+
+    #[test]
+    fn round_trip_generated_trees() {
+        let mut state = 0x9e37_79b9_7f4a_7c15;
+        for case in 0..1000 {
+            let nodes = vec![generate_node(&mut state, case % 6)];
+            check_round_trip(&nodes)
+                .unwrap_or_else(|err| panic!("generated tree {}: {:#}", case, err));
+        }
+    }
+
+    #[test]
+    fn round_trip_deep_tree() {
+        let depth = 500_000;
+        let mut s = "1 1 ".repeat(depth);
+        s.push_str("0 1 5");
+        s.push_str(&" 1".repeat(depth));
+        let nodes = construct_nodes(&s).unwrap();
+        check_round_trip(&nodes).unwrap();
+    }
+
+    #[test]
+    fn different_trees_are_detected() {
+        let nodes = construct_nodes("1 1 0 1 5 1").unwrap();
+        let other_nodes = construct_nodes("1 1 0 1 6 1").unwrap();
+        assert!(same_trees(&nodes, &nodes));
+        assert!(!same_trees(&nodes, &other_nodes));
+    }
+}
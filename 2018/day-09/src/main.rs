@@ -1,33 +1,194 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Error, Result};
 use regex::Regex;
 use std::{
+    collections::VecDeque,
+    env,
     fs::File,
     io::{self, BufRead, BufReader, Write},
+    str::FromStr,
 };
 
-fn calculate_highest_point(num_player: usize, last_point: u32) -> u64 {
-    let mut points: Vec<u64> = vec![0; num_player];
-    let mut game_arena = Vec::with_capacity(last_point as usize);
-    game_arena.push(0);
-    let mut current = 0usize;
+struct Circle<T> {
+    items: VecDeque<T>,
+}
+
+impl<T> Circle<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        Circle {
+            items: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    fn rotate(&mut self, offset: isize) {
+        if self.items.is_empty() {
+            return;
+        }
+        let offset = offset.rem_euclid(self.items.len() as isize) as usize;
+        if offset <= self.items.len() / 2 {
+            self.items.rotate_left(offset);
+        } else {
+            self.items.rotate_right(self.items.len() - offset);
+        }
+    }
+
+    fn insert(&mut self, item: T) {
+        self.items.push_back(item);
+    }
+
+    fn remove(&mut self) -> Option<T> {
+        let item = self.items.pop_back();
+        self.rotate(1);
+        item
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+}
+
+struct GameRules {
+    scoring_divisor: u32,
+    removal_offset: isize,
+    insert_offset: isize,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules {
+            scoring_divisor: 23,
+            removal_offset: 7,
+            insert_offset: 1,
+        }
+    }
+}
+
+impl FromStr for GameRules {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s.split(',').map(|v| v.trim()).collect::<Vec<_>>();
+        if values.len() != 3 {
+            return Err(anyhow!(
+                "expected scoring divisor, removal offset and insert offset"
+            ));
+        }
+        let rules = GameRules {
+            scoring_divisor: values[0].parse()?,
+            removal_offset: values[1].parse()?,
+            insert_offset: values[2].parse()?,
+        };
+        if rules.scoring_divisor < 2 {
+            return Err(anyhow!("scoring divisor must be at least 2"));
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Default)]
+struct GameOptions {
+    timeline: bool,
+    frames: bool,
+}
+
+struct Turn {
+    marble: u32,
+    player: usize,
+    gained: u64,
+    score: u64,
+}
+
+struct GameResult {
+    scores: Vec<u64>,
+    winner: usize,
+    timeline: Vec<Turn>,
+    frames: Vec<String>,
+}
+
+fn render_circle(player: Option<usize>, circle: &Circle<u32>) -> String {
+    let marbles = circle.iter().cloned().collect::<Vec<_>>();
+    let start = marbles.iter().position(|&m| m == 0).unwrap_or(0);
+    let current = marbles.len() - 1;
+    let mut frame = match player {
+        Some(player) => format!("[{}]", player + 1),
+        None => "[-]".to_string(),
+    };
+    for idx in (start..marbles.len()).chain(0..start) {
+        if idx == current {
+            frame.push_str(&format!("({:>2})", marbles[idx]));
+        } else {
+            frame.push_str(&format!(" {:>2} ", marbles[idx]));
+        }
+    }
+    frame
+}
+
+fn play_game(
+    num_player: usize,
+    last_point: u32,
+    rules: &GameRules,
+    options: &GameOptions,
+) -> GameResult {
+    let mut scores: Vec<u64> = vec![0; num_player];
+    let mut timeline = Vec::new();
+    let mut frames = Vec::new();
+    let mut game_arena = Circle::with_capacity(last_point as usize + 1);
+    game_arena.insert(0);
+    if options.frames {
+        frames.push(render_circle(None, &game_arena));
+    }
     for marble in 1..=last_point {
-        if (marble % 23) > 0 {
-            current = ((current + 1) % game_arena.len()) + 1;
-            game_arena.insert(current, marble);
+        let player_idx = (marble as usize - 1) % num_player;
+        let mut gained = 0;
+        if marble % rules.scoring_divisor > 0 {
+            game_arena.rotate(rules.insert_offset);
+            game_arena.insert(marble);
         } else {
-            let player_idx = marble as usize % num_player;
-            current = (current + game_arena.len() - 7) % game_arena.len();
-            points[player_idx] += (marble + game_arena.remove(current)) as u64;
+            game_arena.rotate(-rules.removal_offset);
+            gained = marble as u64 + game_arena.remove().unwrap() as u64;
+            scores[player_idx] += gained;
+        }
+        if options.timeline {
+            timeline.push(Turn {
+                marble,
+                player: player_idx,
+                gained,
+                score: scores[player_idx],
+            });
+        }
+        if options.frames {
+            frames.push(render_circle(Some(player_idx), &game_arena));
         }
     }
-    *points.iter().max().unwrap()
+
+    let winner = scores
+        .iter()
+        .enumerate()
+        .max_by(|(idx1, score1), (idx2, score2)| score1.cmp(score2).then(idx2.cmp(idx1)))
+        .map(|(idx, _)| idx)
+        .unwrap_or(0);
+    GameResult {
+        scores,
+        winner,
+        timeline,
+        frames,
+    }
+}
+
+fn calculate_highest_point(num_player: usize, last_point: u32) -> u64 {
+    let result = play_game(
+        num_player,
+        last_point,
+        &GameRules::default(),
+        &GameOptions::default(),
+    );
+    result.scores[result.winner]
 }
 
 fn main() -> Result<()> {
     let file = File::open("2018/day-09/input/input.txt").context("failed to read input file")?;
     let reader = BufReader::new(file);
 
-    if let Some(s) = reader.lines().filter_map(|line| line.ok()).next() {
+    if let Some(s) = reader.lines().map_while(Result::ok).next() {
         let re = Regex::new(
             "(?P<players>[0-9]+) players; last marble is worth (?P<points>[0-9]+) points",
         )
@@ -46,15 +207,70 @@ fn main() -> Result<()> {
                 highest_point
             )?;
 
-            let highest_point = calculate_highest_point(num_player, 100 * last_point);
+            let scale = match env::args().skip_while(|arg| arg != "--scale").nth(1) {
+                Some(scale) => scale.parse().context("invalid scale")?,
+                None => 100,
+            };
+            let scaled_point = last_point.checked_mul(scale).ok_or_else(|| {
+                anyhow!(
+                    "last marble worth {} * {} points is too large",
+                    scale,
+                    last_point
+                )
+            })?;
+            let highest_point = calculate_highest_point(num_player, scaled_point);
             writeln!(
                 io::stdout(),
-                "highest point with {} players and last marble worth 100 * {} points is {}",
+                "highest point with {} players and last marble worth {} * {} points is {}",
                 num_player,
+                scale,
                 last_point,
                 highest_point
             )?;
         }
     }
+    if let Some(example) = env::args().skip_while(|arg| arg != "--example").nth(1) {
+        let values = example.split(',').collect::<Vec<_>>();
+        if values.len() != 2 {
+            return Err(anyhow!("expected example as players,last marble"));
+        }
+        let num_player: usize = values[0].trim().parse()?;
+        let last_point: u32 = values[1].trim().parse()?;
+        if num_player == 0 {
+            return Err(anyhow!("expected at least one player"));
+        }
+        let rules = match env::args().skip_while(|arg| arg != "--rules").nth(1) {
+            Some(rules) => rules.parse()?,
+            None => GameRules::default(),
+        };
+        let options = GameOptions {
+            timeline: true,
+            frames: last_point <= 100,
+        };
+
+        let result = play_game(num_player, last_point, &rules, &options);
+        for frame in &result.frames {
+            writeln!(io::stdout(), "{}", frame)?;
+        }
+        for turn in result.timeline.iter().filter(|turn| turn.gained > 0) {
+            writeln!(
+                io::stdout(),
+                "marble {}: player {} scores {}, total {}",
+                turn.marble,
+                turn.player + 1,
+                turn.gained,
+                turn.score
+            )?;
+        }
+        for (idx, score) in result.scores.iter().enumerate() {
+            writeln!(io::stdout(), "player {}: {}", idx + 1, score)?;
+        }
+        writeln!(
+            io::stdout(),
+            "winner is player {} with {} points",
+            result.winner + 1,
+            result.scores[result.winner]
+        )?;
+    }
     Ok(())
 }
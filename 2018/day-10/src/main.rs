@@ -64,28 +64,263 @@ fn calculate_points(point_changes: &[PointChange], sec: u32) -> Vec<Point> {
         .collect()
 }
 
-fn render_points(points: &[Point]) -> Option<String> {
-    let min_x = points.iter().map(|p| p.x).min().unwrap();
-    let max_x = points.iter().map(|p| p.x).max().unwrap();
-    let min_y = points.iter().map(|p| p.y).min().unwrap();
-    let max_y = points.iter().map(|p| p.y).max().unwrap();
-
-    if (max_x - min_x) < 120 && (max_y - min_y) < 20 {
-        let mut grid = vec![vec![b'.'; 120]; 20];
-        points
+struct BoundingBox {
+    min_x: i32,
+    max_x: i32,
+    min_y: i32,
+    max_y: i32,
+}
+
+impl BoundingBox {
+    fn new(points: &[Point]) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+        Some(BoundingBox {
+            min_x: points.iter().map(|p| p.x).min().unwrap(),
+            max_x: points.iter().map(|p| p.x).max().unwrap(),
+            min_y: points.iter().map(|p| p.y).min().unwrap(),
+            max_y: points.iter().map(|p| p.y).max().unwrap(),
+        })
+    }
+
+    fn width(&self) -> usize {
+        (self.max_x - self.min_x) as usize + 1
+    }
+
+    fn height(&self) -> usize {
+        (self.max_y - self.min_y) as usize + 1
+    }
+
+    fn area(&self) -> u64 {
+        self.width() as u64 * self.height() as u64
+    }
+}
+
+fn calculate_area(point_changes: &[PointChange], sec: u32) -> u64 {
+    BoundingBox::new(&calculate_points(point_changes, sec))
+        .map(|bbox| bbox.area())
+        .unwrap_or(0)
+}
+
+fn estimate_closing_second(
+    point_changes: &[PointChange],
+    axis: fn(&PointChange) -> (i32, i32),
+) -> u32 {
+    let first = point_changes.iter().min_by_key(|pc| axis(pc).0);
+    let last = point_changes.iter().max_by_key(|pc| axis(pc).0);
+    match (first, last) {
+        (Some(first), Some(last)) if axis(first).1 > axis(last).1 => {
+            let distance = (axis(last).0 - axis(first).0) as u32;
+            let closing_speed = (axis(first).1 - axis(last).1) as u32;
+            distance / closing_speed
+        }
+        _ => 0,
+    }
+}
+
+fn estimate_message_second(point_changes: &[PointChange]) -> u32 {
+    let horizontal = estimate_closing_second(point_changes, |pc| (pc.initial_x, pc.velocity_x));
+    let vertical = estimate_closing_second(point_changes, |pc| (pc.initial_y, pc.velocity_y));
+    horizontal.max(vertical)
+}
+
+fn find_message_second(point_changes: &[PointChange]) -> u32 {
+    let mut low = 0u32;
+    let mut high = estimate_message_second(point_changes) * 2 + 2;
+    while calculate_area(point_changes, high + 1) < calculate_area(point_changes, high) {
+        low = high;
+        high *= 2;
+    }
+    while high - low > 2 {
+        let mid_low = low + (high - low) / 3;
+        let mid_high = high - (high - low) / 3;
+        if calculate_area(point_changes, mid_low) <= calculate_area(point_changes, mid_high) {
+            high = mid_high;
+        } else {
+            low = mid_low;
+        }
+    }
+    (low..=high)
+        .min_by_key(|&sec| calculate_area(point_changes, sec))
+        .unwrap()
+}
+
+fn render_points(points: &[Point]) -> String {
+    let bbox = match BoundingBox::new(points) {
+        Some(bbox) => bbox,
+        None => return String::new(),
+    };
+
+    let mut grid = vec![vec![b'.'; bbox.width()]; bbox.height()];
+    points
+        .iter()
+        .map(|p| ((p.x - bbox.min_x) as usize, (p.y - bbox.min_y) as usize))
+        .for_each(|(x, y)| grid[y][x] = b'#');
+    let mut grid_str = String::with_capacity((bbox.width() + 1) * bbox.height());
+    grid.iter()
+        .map(|v| String::from_utf8_lossy(v))
+        .for_each(|s| {
+            grid_str.push_str(&s);
+            grid_str.push('\n');
+        });
+    grid_str
+}
+
+const GLYPH_WIDTH: usize = 6;
+const GLYPH_HEIGHT: usize = 10;
+const GLYPH_SPACING: usize = 2;
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 15] = [
+    (
+        'A',
+        [
+            "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'B',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+            "#....#", "#####.",
+        ],
+    ),
+    (
+        'C',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#....#", ".####.",
+        ],
+    ),
+    (
+        'E',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'F',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'G',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+            "#...##", ".###.#",
+        ],
+    ),
+    (
+        'H',
+        [
+            "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'J',
+        [
+            "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+            "#...#.", ".###..",
+        ],
+    ),
+    (
+        'K',
+        [
+            "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+            "#...#.", "#....#",
+        ],
+    ),
+    (
+        'L',
+        [
+            "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'N',
+        [
+            "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+            "#...##", "#....#",
+        ],
+    ),
+    (
+        'P',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'R',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'X',
+        [
+            "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'Z',
+        [
+            "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+];
+
+fn recognize_glyph(rows: &[String]) -> Option<char> {
+    GLYPHS
+        .iter()
+        .find(|(_, glyph)| glyph.iter().zip(rows).all(|(g, r)| g == r))
+        .map(|(c, _)| *c)
+}
+
+fn recognize_message(points: &[Point]) -> Result<String> {
+    let bbox = BoundingBox::new(points).ok_or_else(|| anyhow!("no points to recognize"))?;
+    if bbox.height() != GLYPH_HEIGHT {
+        return Err(anyhow!(
+            "message is {} rows high, expected {}",
+            bbox.height(),
+            GLYPH_HEIGHT
+        ));
+    }
+
+    let stride = GLYPH_WIDTH + GLYPH_SPACING;
+    let num_glyph = (bbox.width() + GLYPH_SPACING).div_ceil(stride);
+    let mut grid = vec![vec![b'.'; num_glyph * stride]; GLYPH_HEIGHT];
+    points
+        .iter()
+        .map(|p| ((p.x - bbox.min_x) as usize, (p.y - bbox.min_y) as usize))
+        .for_each(|(x, y)| grid[y][x] = b'#');
+
+    let mut message = String::with_capacity(num_glyph);
+    let mut unknowns = Vec::new();
+    for idx in 0..num_glyph {
+        let start = idx * stride;
+        let rows = grid
             .iter()
-            .map(|p| ((p.x - min_x) as usize, (p.y - min_y) as usize))
-            .for_each(|(x, y)| grid[y][x] = b'#');
-        let mut grid_str = String::with_capacity(121 * 20);
-        grid.iter()
-            .map(|v| String::from_utf8_lossy(&v))
-            .for_each(|s| {
-                grid_str.push_str(&s);
-                grid_str.push('\n');
-            });
-        Some(grid_str)
+            .map(|row| String::from_utf8_lossy(&row[start..start + GLYPH_WIDTH]).into_owned())
+            .collect::<Vec<_>>();
+        match recognize_glyph(&rows) {
+            Some(c) => message.push(c),
+            None => unknowns.push(format!("glyph {}:\n{}", idx, rows.join("\n"))),
+        }
+    }
+
+    if unknowns.is_empty() {
+        Ok(message)
     } else {
-        None
+        Err(anyhow!("unknown glyphs\n{}", unknowns.join("\n")))
     }
 }
 
@@ -98,28 +333,10 @@ fn main() -> Result<()> {
         .filter_map(|line| line.ok().and_then(|s| s.parse::<PointChange>().ok()))
         .collect::<Vec<_>>();
 
-    let mut sec = 0u32;
-    let mut in_range = false;
-    while !in_range {
-        let points = calculate_points(&point_changes, sec);
-        if let Some(grid) = render_points(&points) {
-            in_range = true;
-            writeln!(io::stdout(), "grid at {} second(s)", sec)?;
-            writeln!(io::stdout(), "{}", grid)?;
-            writeln!(io::stdout(), "{}", "~".repeat(120))?;
-        }
-        sec += 1;
-    }
-    loop {
-        let points = calculate_points(&point_changes, sec);
-        if let Some(grid) = render_points(&points) {
-            writeln!(io::stdout(), "grid at {} second(s)", sec)?;
-            writeln!(io::stdout(), "{}", grid)?;
-            writeln!(io::stdout(), "{}", "~".repeat(120))?;
-        } else {
-            break;
-        }
-        sec += 1;
-    }
+    let sec = find_message_second(&point_changes);
+    let points = calculate_points(&point_changes, sec);
+    writeln!(io::stdout(), "grid at {} second(s)", sec)?;
+    writeln!(io::stdout(), "{}", render_points(&points))?;
+    writeln!(io::stdout(), "message is {}", recognize_message(&points)?)?;
     Ok(())
 }